@@ -0,0 +1,57 @@
+#![no_main]
+
+/*
+cargo +nightly fuzz run read -- -max_len=65536
+
+Seed the corpus with the files in sample_aseprite_files, the reader checks
+the file size against the input so mutations of the header mostly stop there.
+The width and height keep their default limits so that a header of the
+largest size is read, the image size limit has to stop the renderers from
+allocating its canvas.
+*/
+
+use std::io::Cursor;
+
+use ase::{
+    Aseprite, ChunkData, Limits, ReadMode, ReadOptions, SheetOptions, Sprite,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let options = ReadOptions {
+        limits: Limits {
+            max_decompressed_size: 16 << 20,
+            max_image_size: 64 << 20,
+            ..Limits::default()
+        },
+        mode: ReadMode::Lenient,
+    };
+    let ase =
+        match Aseprite::from_read_with_options(&mut Cursor::new(data), &options)
+        {
+            Ok(ase) => ase,
+            Err(_) => return,
+        };
+
+    let color_depth = ase.header.color_depth;
+    for chunk in ase.frames.iter().flat_map(|frame| frame.chunks.iter()) {
+        match &chunk.chunk_data {
+            ChunkData::CelChunk(cel) => {
+                let _ = cel.image(&color_depth);
+                let _ = cel.cel.tilemap();
+            }
+            ChunkData::TilesetChunk(tileset) => {
+                let _ = tileset.image(&color_depth);
+            }
+            _ => (),
+        }
+    }
+
+    let sprite = Sprite::from_aseprite_with_limits(ase, options.limits);
+    for frame in 0..sprite.frame_count() {
+        let _ = sprite.render_frame_srgb(frame);
+    }
+    let _ = sprite.sprite_sheet(&SheetOptions::default());
+    let _ = sprite.write_gif(&mut Vec::new());
+    let _ = sprite.aseprite().write(&mut Cursor::new(Vec::new()));
+});
@@ -0,0 +1,366 @@
+use crate::color::RGBA256;
+use crate::helpers::mul_un8;
+use crate::BlendMode;
+
+/// blend `src` over `backdrop` with `opacity` using the given layer blend
+/// mode, following Aseprite's new blend functions (the `_n` blenders): over
+/// a semi-transparent backdrop the result goes from the normal blend to the
+/// blend mode as the backdrop gets opaque, over an opaque backdrop it is the
+/// same as `blend_legacy`
+pub fn blend(
+    mode: BlendMode,
+    backdrop: RGBA256,
+    src: RGBA256,
+    opacity: u8,
+) -> RGBA256 {
+    // like Aseprite's renderer, nothing to blend with on transparent pixels
+    if backdrop.a == 0 || mode == BlendMode::Normal {
+        return blend_normal(backdrop, src, opacity);
+    }
+
+    let normal = blend_normal(backdrop, src, opacity);
+    let blended = blend_legacy(mode, backdrop, src, opacity);
+    let normal_to_blended = blend_merge(normal, blended, backdrop.a);
+    let composite_alpha = mul_un8(backdrop.a, mul_un8(src.a, opacity));
+    blend_merge(normal_to_blended, blended, composite_alpha)
+}
+
+/// blend `src` over `backdrop` with `opacity` using the given layer blend
+/// mode, following Aseprite's legacy blend functions which blend with the
+/// backdrop color whatever its alpha
+pub fn blend_legacy(
+    mode: BlendMode,
+    backdrop: RGBA256,
+    src: RGBA256,
+    opacity: u8,
+) -> RGBA256 {
+    if backdrop.a == 0 {
+        return blend_normal(backdrop, src, opacity);
+    }
+
+    let src = match mode {
+        BlendMode::Normal => return blend_normal(backdrop, src, opacity),
+        BlendMode::Multiply => blend_channels(backdrop, src, multiply),
+        BlendMode::Screen => blend_channels(backdrop, src, screen),
+        BlendMode::Overlay => blend_channels(backdrop, src, overlay),
+        BlendMode::Darken => blend_channels(backdrop, src, |b, s| b.min(s)),
+        BlendMode::Lighten => blend_channels(backdrop, src, |b, s| b.max(s)),
+        BlendMode::ColorDodge => blend_channels(backdrop, src, color_dodge),
+        BlendMode::ColorBurn => blend_channels(backdrop, src, color_burn),
+        BlendMode::HardLight => blend_channels(backdrop, src, hard_light),
+        BlendMode::SoftLight => blend_channels(backdrop, src, soft_light),
+        BlendMode::Difference => {
+            blend_channels(backdrop, src, |b, s| (b - s).abs())
+        }
+        BlendMode::Exclusion => blend_channels(backdrop, src, exclusion),
+        BlendMode::Hue => blend_hue(backdrop, src),
+        BlendMode::Saturation => blend_saturation(backdrop, src),
+        BlendMode::Color => blend_color(backdrop, src),
+        BlendMode::Luminosity => blend_luminosity(backdrop, src),
+        BlendMode::Addition => {
+            blend_channels(backdrop, src, |b, s| (b + s).min(255))
+        }
+        BlendMode::Subtract => {
+            blend_channels(backdrop, src, |b, s| (b - s).max(0))
+        }
+        BlendMode::Divide => blend_channels(backdrop, src, divide),
+    };
+
+    blend_normal(backdrop, src, opacity)
+}
+
+/// blend `src` over `backdrop` with `opacity` like Aseprite's normal
+/// blend mode
+pub fn blend_normal(backdrop: RGBA256, src: RGBA256, opacity: u8) -> RGBA256 {
+    if backdrop.a == 0 {
+        return RGBA256 {
+            a: mul_un8(src.a, opacity),
+            ..src
+        };
+    } else if src.a == 0 {
+        return backdrop;
+    }
+
+    let ba = backdrop.a as i32;
+    let sa = mul_un8(src.a, opacity) as i32;
+    let ra = sa + ba - mul_un8(ba as u8, sa as u8) as i32;
+    let channel = |b: u8, s: u8| {
+        let b = b as i32;
+        (b + (s as i32 - b) * sa / ra) as u8
+    };
+
+    RGBA256 {
+        r: channel(backdrop.r, src.r),
+        g: channel(backdrop.g, src.g),
+        b: channel(backdrop.b, src.b),
+        a: ra as u8,
+    }
+}
+
+/// interpolate from `backdrop` to `src` by `opacity`, alpha included, like
+/// Aseprite's merge blender
+fn blend_merge(backdrop: RGBA256, src: RGBA256, opacity: u8) -> RGBA256 {
+    let opacity = opacity as i32;
+    let channel = |b: u8, s: u8| {
+        let b = b as i32;
+        (b + mul_signed(s as i32 - b, opacity)) as u8
+    };
+    let a = channel(backdrop.a, src.a);
+    if a == 0 {
+        return RGBA256 {
+            r: 0,
+            g: 0,
+            b: 0,
+            a,
+        };
+    }
+    let color = if backdrop.a == 0 {
+        src
+    } else if src.a == 0 {
+        backdrop
+    } else {
+        RGBA256 {
+            r: channel(backdrop.r, src.r),
+            g: channel(backdrop.g, src.g),
+            b: channel(backdrop.b, src.b),
+            a,
+        }
+    };
+    RGBA256 { a, ..color }
+}
+
+/// apply `f` to each color channel, keeping the alpha of `src`
+fn blend_channels<F>(backdrop: RGBA256, src: RGBA256, f: F) -> RGBA256
+where
+    F: Fn(i32, i32) -> i32,
+{
+    let channel = |b: u8, s: u8| f(b as i32, s as i32).clamp(0, 255) as u8;
+    RGBA256 {
+        r: channel(backdrop.r, src.r),
+        g: channel(backdrop.g, src.g),
+        b: channel(backdrop.b, src.b),
+        a: src.a,
+    }
+}
+
+fn mul(a: i32, b: i32) -> i32 {
+    mul_un8(a as u8, b as u8) as i32
+}
+
+/// `mul` for a negative `a`, rounding like Aseprite's MUL_UN8 does
+fn mul_signed(a: i32, b: i32) -> i32 {
+    let t = a * b + 0x80;
+    ((t >> 8) + t) >> 8
+}
+
+fn div(a: i32, b: i32) -> i32 {
+    (a * 0xff + b / 2) / b
+}
+
+fn multiply(b: i32, s: i32) -> i32 {
+    mul(b, s)
+}
+
+fn screen(b: i32, s: i32) -> i32 {
+    b + s - mul(b, s)
+}
+
+fn hard_light(b: i32, s: i32) -> i32 {
+    if s < 128 {
+        multiply(b, s << 1)
+    } else {
+        screen(b, (s << 1) - 255)
+    }
+}
+
+fn overlay(b: i32, s: i32) -> i32 {
+    hard_light(s, b)
+}
+
+fn color_dodge(b: i32, s: i32) -> i32 {
+    if b == 0 {
+        return 0;
+    }
+    let s = 255 - s;
+    if b >= s {
+        255
+    } else {
+        div(b, s)
+    }
+}
+
+fn color_burn(b: i32, s: i32) -> i32 {
+    if b == 255 {
+        return 255;
+    }
+    let b = 255 - b;
+    if b >= s {
+        0
+    } else {
+        255 - div(b, s)
+    }
+}
+
+fn soft_light(b: i32, s: i32) -> i32 {
+    let b = b as f64 / 255.0;
+    let s = s as f64 / 255.0;
+    let d = if b <= 0.25 {
+        ((16.0 * b - 12.0) * b + 4.0) * b
+    } else {
+        b.sqrt()
+    };
+    let r = if s <= 0.5 {
+        b - (1.0 - 2.0 * s) * b * (1.0 - b)
+    } else {
+        b + (2.0 * s - 1.0) * (d - b)
+    };
+    (r * 255.0 + 0.5) as i32
+}
+
+fn exclusion(b: i32, s: i32) -> i32 {
+    b + s - 2 * mul(b, s)
+}
+
+fn divide(b: i32, s: i32) -> i32 {
+    if b == 0 {
+        0
+    } else if b >= s {
+        255
+    } else {
+        div(b, s)
+    }
+}
+
+type Rgb = [f64; 3];
+
+fn to_rgb(c: RGBA256) -> Rgb {
+    [c.r as f64 / 255.0, c.g as f64 / 255.0, c.b as f64 / 255.0]
+}
+
+fn from_rgb(c: Rgb, a: u8) -> RGBA256 {
+    let channel = |v: f64| (255.0 * v).clamp(0.0, 255.0) as u8;
+    RGBA256 {
+        r: channel(c[0]),
+        g: channel(c[1]),
+        b: channel(c[2]),
+        a,
+    }
+}
+
+fn lum(c: &Rgb) -> f64 {
+    0.3 * c[0] + 0.59 * c[1] + 0.11 * c[2]
+}
+
+fn sat(c: &Rgb) -> f64 {
+    c[0].max(c[1].max(c[2])) - c[0].min(c[1].min(c[2]))
+}
+
+// index of the channel picked by Aseprite's MIN/MAX/MID macros, ties
+// resolve to the same channels so the results match on gray colors too
+fn min_index(c: &Rgb) -> usize {
+    let gb = if c[1] < c[2] { 1 } else { 2 };
+    if c[0] < c[gb] {
+        0
+    } else {
+        gb
+    }
+}
+
+fn max_index(c: &Rgb) -> usize {
+    let gb = if c[1] > c[2] { 1 } else { 2 };
+    if c[0] > c[gb] {
+        0
+    } else {
+        gb
+    }
+}
+
+fn mid_index(c: &Rgb) -> usize {
+    let (x, y, z) = (c[0], c[1], c[2]);
+    if x > y {
+        if y > z {
+            1
+        } else if x > z {
+            2
+        } else {
+            0
+        }
+    } else if y > z {
+        if z > x {
+            2
+        } else {
+            0
+        }
+    } else {
+        1
+    }
+}
+
+fn clip_color(c: &mut Rgb) {
+    let l = lum(c);
+    let n = c[0].min(c[1].min(c[2]));
+    let x = c[0].max(c[1].max(c[2]));
+    if n < 0.0 {
+        for v in c.iter_mut() {
+            *v = l + (((*v - l) * l) / (l - n));
+        }
+    }
+    if x > 1.0 {
+        for v in c.iter_mut() {
+            *v = l + (((*v - l) * (1.0 - l)) / (x - l));
+        }
+    }
+}
+
+fn set_lum(c: &mut Rgb, l: f64) {
+    let d = l - lum(c);
+    for v in c.iter_mut() {
+        *v += d;
+    }
+    clip_color(c);
+}
+
+fn set_sat(c: &mut Rgb, s: f64) {
+    let min = min_index(c);
+    let mid = mid_index(c);
+    let max = max_index(c);
+    if c[max] > c[min] {
+        c[mid] = ((c[mid] - c[min]) * s) / (c[max] - c[min]);
+        c[max] = s;
+    } else {
+        c[mid] = 0.0;
+        c[max] = 0.0;
+    }
+    c[min] = 0.0;
+}
+
+fn blend_hue(backdrop: RGBA256, src: RGBA256) -> RGBA256 {
+    let b = to_rgb(backdrop);
+    let mut c = to_rgb(src);
+    set_sat(&mut c, sat(&b));
+    set_lum(&mut c, lum(&b));
+    from_rgb(c, src.a)
+}
+
+fn blend_saturation(backdrop: RGBA256, src: RGBA256) -> RGBA256 {
+    let s = to_rgb(src);
+    let mut c = to_rgb(backdrop);
+    let l = lum(&c);
+    set_sat(&mut c, sat(&s));
+    set_lum(&mut c, l);
+    from_rgb(c, src.a)
+}
+
+fn blend_color(backdrop: RGBA256, src: RGBA256) -> RGBA256 {
+    let b = to_rgb(backdrop);
+    let mut c = to_rgb(src);
+    set_lum(&mut c, lum(&b));
+    from_rgb(c, src.a)
+}
+
+fn blend_luminosity(backdrop: RGBA256, src: RGBA256) -> RGBA256 {
+    let s = to_rgb(src);
+    let mut c = to_rgb(backdrop);
+    set_lum(&mut c, lum(&s));
+    from_rgb(c, src.a)
+}
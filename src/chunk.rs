@@ -1,7 +1,9 @@
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use std::io::{self, Cursor, Read, Seek, Write};
+use std::io::{Cursor, Read, Seek, Write};
 
-use crate::Header;
+use crate::error::{Error, Location, Result};
+use crate::helpers::{block_size, read_bytes};
+use crate::{Header, ReadMode, ReadOptions, WriteOptions};
 
 pub mod cel_chunk;
 pub use self::cel_chunk::*;
@@ -9,6 +11,8 @@ pub mod cel_extra_chunk;
 pub use self::cel_extra_chunk::*;
 pub mod color_profile_chunk;
 pub use self::color_profile_chunk::*;
+pub mod external_files_chunk;
+pub use self::external_files_chunk::*;
 pub mod frame_tags_chunk;
 pub use self::frame_tags_chunk::*;
 pub mod layer_chunk;
@@ -25,6 +29,8 @@ pub mod path_chunk;
 pub use self::path_chunk::*;
 pub mod slice_chunk;
 pub use self::slice_chunk::*;
+pub mod tileset_chunk;
+pub use self::tileset_chunk::*;
 pub mod user_data_chunk;
 pub use self::user_data_chunk::*;
 
@@ -36,18 +42,167 @@ pub enum ChunkData {
     CelChunk(CelChunk),
     CelExtraChunk(CelExtraChunk),
     ColorProfileChunk(ColorProfileChunk),
+    ExternalFilesChunk(ExternalFilesChunk),
     MaskChunk(MaskChunk),
     PathChunk(PathChunk),
     FrameTagsChunk(FrameTagsChunk),
     PaletteChunk(PaletteChunk),
     UserDataChunk(UserDataChunk),
     SliceChunk(SliceChunk),
+    TilesetChunk(TilesetChunk),
+    /// chunk of a type this crate doesn't know, kept as its raw payload
+    Unknown {
+        chunk_type: u16,
+        bytes: Vec<u8>,
+    },
+}
+
+fn fill_user_data_slot(
+    slot: &mut Option<UserDataChunk>,
+    user_data: UserDataChunk,
+) -> Option<UserDataChunk> {
+    if slot.is_some() {
+        return Some(user_data);
+    }
+    *slot = Some(user_data);
+    None
 }
 
 impl ChunkData {
-    pub fn write<W>(&self, wtr: &mut W) -> io::Result<()>
+    pub fn chunk_type(&self) -> u16 {
+        match self {
+            ChunkData::OldPaletteChunk4(_) => 0x0004,
+            ChunkData::OldPaletteChunk11(_) => 0x0011,
+            ChunkData::LayerChunk(_) => 0x2004,
+            ChunkData::CelChunk(_) => 0x2005,
+            ChunkData::CelExtraChunk(_) => 0x2006,
+            ChunkData::ColorProfileChunk(_) => 0x2007,
+            ChunkData::ExternalFilesChunk(_) => 0x2008,
+            ChunkData::MaskChunk(_) => 0x2016,
+            ChunkData::PathChunk(_) => 0x2017,
+            ChunkData::FrameTagsChunk(_) => 0x2018,
+            ChunkData::PaletteChunk(_) => 0x2019,
+            ChunkData::UserDataChunk(_) => 0x2020,
+            ChunkData::SliceChunk(_) => 0x2022,
+            ChunkData::TilesetChunk(_) => 0x2023,
+            ChunkData::Unknown { chunk_type, .. } => *chunk_type,
+        }
+    }
+
+    /// attach a User Data chunk that was read right after this chunk,
+    /// gives it back when this chunk can not hold (more) user data
+    pub fn attach_user_data(
+        &mut self,
+        user_data: UserDataChunk,
+    ) -> Option<UserDataChunk> {
+        use self::ChunkData::*;
+        match self {
+            LayerChunk(inner) => {
+                fill_user_data_slot(&mut inner.user_data, user_data)
+            }
+            CelChunk(inner) => {
+                fill_user_data_slot(&mut inner.user_data, user_data)
+            }
+            PaletteChunk(inner) => {
+                fill_user_data_slot(&mut inner.user_data, user_data)
+            }
+            SliceChunk(inner) => {
+                fill_user_data_slot(&mut inner.user_data, user_data)
+            }
+            FrameTagsChunk(inner) => {
+                match inner.tags.iter_mut().find(|t| t.user_data.is_none()) {
+                    Some(tag) => {
+                        fill_user_data_slot(&mut tag.user_data, user_data)
+                    }
+                    None => Some(user_data),
+                }
+            }
+            TilesetChunk(inner) => {
+                if inner.user_data.is_none() {
+                    fill_user_data_slot(&mut inner.user_data, user_data)
+                } else if inner.tiles_user_data.len()
+                    < inner.number_of_tiles as usize
+                {
+                    inner.tiles_user_data.push(user_data);
+                    None
+                } else {
+                    Some(user_data)
+                }
+            }
+            _ => Some(user_data),
+        }
+    }
+
+    /// Aseprite writes Cel Extra and old palette chunks between a chunk and
+    /// its User Data, so they don't take the user data that follows them
+    pub(crate) fn is_skipped_by_user_data(&self) -> bool {
+        matches!(
+            self,
+            ChunkData::CelExtraChunk(_)
+                | ChunkData::OldPaletteChunk4(_)
+                | ChunkData::OldPaletteChunk11(_)
+        )
+    }
+
+    /// remove the attached User Data chunks, in the order they are written
+    pub(crate) fn take_user_data(&mut self) -> Vec<UserDataChunk> {
+        use self::ChunkData::*;
+        match self {
+            LayerChunk(inner) => inner.user_data.take().into_iter().collect(),
+            CelChunk(inner) => inner.user_data.take().into_iter().collect(),
+            PaletteChunk(inner) => inner.user_data.take().into_iter().collect(),
+            SliceChunk(inner) => inner.user_data.take().into_iter().collect(),
+            FrameTagsChunk(inner) => inner
+                .tags
+                .iter_mut()
+                .filter_map(|t| t.user_data.take())
+                .collect(),
+            TilesetChunk(inner) => {
+                let mut user_data: Vec<_> =
+                    inner.user_data.take().into_iter().collect();
+                user_data.append(&mut inner.tiles_user_data);
+                user_data
+            }
+            _ => vec![],
+        }
+    }
+
+    /// User Data chunks to be written right after this chunk,
+    /// None stands for an empty one that keeps the following in place
+    pub fn attached_user_data(&self) -> Vec<Option<&UserDataChunk>> {
+        use self::ChunkData::*;
+        match self {
+            LayerChunk(inner) => inner.user_data.iter().map(Some).collect(),
+            CelChunk(inner) => inner.user_data.iter().map(Some).collect(),
+            PaletteChunk(inner) => inner.user_data.iter().map(Some).collect(),
+            SliceChunk(inner) => inner.user_data.iter().map(Some).collect(),
+            FrameTagsChunk(inner) => {
+                let len = inner
+                    .tags
+                    .iter()
+                    .rposition(|t| t.user_data.is_some())
+                    .map_or(0, |i| i + 1);
+                inner.tags[..len]
+                    .iter()
+                    .map(|t| t.user_data.as_ref())
+                    .collect()
+            }
+            TilesetChunk(inner) => {
+                if inner.user_data.is_none() && inner.tiles_user_data.is_empty()
+                {
+                    return vec![];
+                }
+                let mut user_data = vec![inner.user_data.as_ref()];
+                user_data.extend(inner.tiles_user_data.iter().map(Some));
+                user_data
+            }
+            _ => vec![],
+        }
+    }
+
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
     where
-        W: Write + Seek,
+        W: Write,
     {
         use self::ChunkData::*;
         match self {
@@ -57,12 +212,18 @@ impl ChunkData {
             CelChunk(inner) => inner.write(wtr),
             CelExtraChunk(inner) => inner.write(wtr),
             ColorProfileChunk(inner) => inner.write(wtr),
+            ExternalFilesChunk(inner) => inner.write(wtr),
             MaskChunk(inner) => inner.write(wtr),
             PathChunk(inner) => inner.write(wtr),
             FrameTagsChunk(inner) => inner.write(wtr),
             PaletteChunk(inner) => inner.write(wtr),
             UserDataChunk(inner) => inner.write(wtr),
             SliceChunk(inner) => inner.write(wtr),
+            TilesetChunk(inner) => inner.write(wtr),
+            Unknown { bytes, .. } => {
+                wtr.write_all(bytes)?;
+                Ok(())
+            }
         }
     }
 }
@@ -81,13 +242,47 @@ impl Chunk {
         }
     }
 
-    pub fn from_read<R>(read: &mut R, header: &Header) -> io::Result<Self>
+    pub fn from_read<R>(read: &mut R, header: &Header) -> Result<Self>
+    where
+        R: Read + Seek,
+    {
+        Chunk::from_read_with_options(read, header, &ReadOptions::default())
+    }
+
+    /// errors are located at the start of the chunk
+    pub fn from_read_with_options<R>(
+        read: &mut R,
+        header: &Header,
+        options: &ReadOptions,
+    ) -> Result<Self>
+    where
+        R: Read + Seek,
+    {
+        let offset = read.stream_position().ok();
+        Self::read_chunk(read, header, options).map_err(|e| e.at_offset(offset))
+    }
+
+    fn read_chunk<R>(
+        read: &mut R,
+        header: &Header,
+        options: &ReadOptions,
+    ) -> Result<Self>
     where
         R: Read + Seek,
     {
         let chunk_size = read.read_u32::<LittleEndian>()?;
         let chunk_type = read.read_u16::<LittleEndian>()?;
-        let chunk_data_size = chunk_size - 4 - 2;
+        let chunk_data_size =
+            chunk_size.checked_sub(4 + 2).ok_or_else(|| {
+                Error::invalid_data(format!(
+                    "Chunk size {} is smaller than the chunk header",
+                    chunk_size
+                ))
+            })?;
+
+        // parse the chunk from its own buffer so it can't read past its end
+        let mut data = Cursor::new(read_bytes(read, chunk_data_size as usize)?);
+        let read = &mut data;
 
         let chunk_data = match chunk_type {
             0x0004 => {
@@ -97,15 +292,19 @@ impl Chunk {
                 OldPaletteChunk11::from_read(read)?,
             ),
             0x2004 => ChunkData::LayerChunk(LayerChunk::from_read(read)?),
-            0x2005 => ChunkData::CelChunk(CelChunk::from_read(
+            0x2005 => ChunkData::CelChunk(CelChunk::from_read_with_limits(
                 read,
                 chunk_data_size,
                 header,
+                &options.limits,
             )?),
             0x2006 => ChunkData::CelExtraChunk(CelExtraChunk::from_read(read)?),
             0x2007 => ChunkData::ColorProfileChunk(
                 ColorProfileChunk::from_read(read)?,
             ),
+            0x2008 => ChunkData::ExternalFilesChunk(
+                ExternalFilesChunk::from_read(read)?,
+            ),
             0x2016 => ChunkData::MaskChunk(MaskChunk::from_read(read)?),
             0x2017 => ChunkData::PathChunk(PathChunk::from_read(
                 read,
@@ -114,17 +313,43 @@ impl Chunk {
             0x2018 => {
                 ChunkData::FrameTagsChunk(FrameTagsChunk::from_read(read)?)
             }
-            0x2019 => ChunkData::PaletteChunk(PaletteChunk::from_read(read)?),
+            0x2019 => {
+                ChunkData::PaletteChunk(PaletteChunk::from_read_with_limits(
+                    read,
+                    chunk_data_size,
+                    &options.limits,
+                )?)
+            }
             0x2020 => ChunkData::UserDataChunk(UserDataChunk::from_read(read)?),
             0x2022 => ChunkData::SliceChunk(SliceChunk::from_read(read)?),
+            0x2023 => {
+                ChunkData::TilesetChunk(TilesetChunk::from_read_with_limits(
+                    read,
+                    header.color_depth,
+                    &options.limits,
+                )?)
+            }
+            _ if options.mode == ReadMode::Lenient => {
+                let mut bytes = Vec::with_capacity(chunk_data_size as usize);
+                read.read_to_end(&mut bytes)?;
+                ChunkData::Unknown { chunk_type, bytes }
+            }
             _ => {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("Invalid Chunk Type 0x{:X}", chunk_type),
-                ));
+                return Err(Error::UnknownChunkType {
+                    chunk_type,
+                    location: Location::default(),
+                });
             }
         };
 
+        if data.position() != chunk_data_size as u64 {
+            return Err(Error::invalid_data(format!(
+                "Chunk is {} bytes but {} were read",
+                chunk_data_size,
+                data.position()
+            )));
+        }
+
         let chunk = Chunk {
             chunk_size,
             chunk_data,
@@ -133,33 +358,89 @@ impl Chunk {
         Ok(chunk)
     }
 
-    pub fn write<W>(&self, wtr: &mut W) -> io::Result<()>
+    /// number of chunks written by `write`, including attached User Data
+    pub fn chunk_count(&self) -> usize {
+        1 + self.chunk_data.attached_user_data().len()
+    }
+
+    fn write_chunk<W, F>(
+        wtr: &mut W,
+        chunk_type: u16,
+        write_data: F,
+    ) -> Result<()>
     where
-        W: Write + Seek,
+        W: Write,
+        F: FnOnce(&mut Vec<u8>) -> Result<()>,
     {
-        let chunk_buf = vec![];
-        let mut chunk_wtr = Cursor::new(chunk_buf);
-        self.chunk_data.write(&mut chunk_wtr)?;
-        let chunk_size = chunk_wtr.position() as u32;
+        let mut chunk_buf = vec![];
+        write_data(&mut chunk_buf)?;
 
-        wtr.write_u32::<LittleEndian>(6 + chunk_size)?;
-        let chunk_type = match self.chunk_data {
-            ChunkData::OldPaletteChunk4(_) => 0x0004,
-            ChunkData::OldPaletteChunk11(_) => 0x0011,
-            ChunkData::LayerChunk(_) => 0x2004,
-            ChunkData::CelChunk(_) => 0x2005,
-            ChunkData::CelExtraChunk(_) => 0x2006,
-            ChunkData::ColorProfileChunk(_) => 0x2007,
-            ChunkData::MaskChunk(_) => 0x2016,
-            ChunkData::PathChunk(_) => 0x2017,
-            ChunkData::FrameTagsChunk(_) => 0x2018,
-            ChunkData::PaletteChunk(_) => 0x2019,
-            ChunkData::UserDataChunk(_) => 0x2020,
-            ChunkData::SliceChunk(_) => 0x2022,
-        };
+        wtr.write_u32::<LittleEndian>(block_size(
+            "Chunk",
+            6,
+            chunk_buf.len(),
+        )?)?;
         wtr.write_u16::<LittleEndian>(chunk_type)?;
+        wtr.write_all(&chunk_buf)?;
+        Ok(())
+    }
+
+    /// write the chunk and its User Data, cels are written as they are
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        self.write_data(wtr, None)?;
+        self.write_user_data(wtr)
+    }
 
-        wtr.write(&chunk_wtr.into_inner())?;
+    /// write the chunk and its User Data, `header` gives the color depth of
+    /// cels
+    pub fn write_with_options<W>(
+        &self,
+        wtr: &mut W,
+        header: &Header,
+        options: &WriteOptions,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        self.write_data(wtr, Some((header, options)))?;
+        self.write_user_data(wtr)
+    }
+
+    /// write the chunk without its User Data, cels are written as they are
+    /// when there are no options
+    pub(crate) fn write_data<W>(
+        &self,
+        wtr: &mut W,
+        options: Option<(&Header, &WriteOptions)>,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        let chunk_type = self.chunk_data.chunk_type();
+        Chunk::write_chunk(wtr, chunk_type, |w| {
+            match (&self.chunk_data, options) {
+                (ChunkData::CelChunk(cel), Some((header, options))) => {
+                    cel.write_with_options(w, &header.color_depth, options)
+                }
+                (chunk_data, _) => chunk_data.write(w),
+            }
+        })
+    }
+
+    /// write the User Data chunks attached to this chunk
+    pub(crate) fn write_user_data<W>(&self, wtr: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        for user_data in self.chunk_data.attached_user_data() {
+            Chunk::write_chunk(wtr, 0x2020, |w| match user_data {
+                Some(user_data) => user_data.write(w),
+                None => UserDataChunk::default().write(w),
+            })?;
+        }
         Ok(())
     }
 }
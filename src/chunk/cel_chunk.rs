@@ -1,12 +1,27 @@
-use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
-use flate2::read::ZlibDecoder;
+use crate::color::Pixels;
+use crate::error::{Error, Location, Result};
+use crate::helpers::{
+    read_bytes, write_zeros, zlib_compress, zlib_decompress_bounded,
+};
+use crate::image::Image;
+use crate::{
+    CelCompression, ColorDepth, Header, Limits, UserDataChunk, WriteOptions,
+};
 
-use crate::color::{Pixels};
-use crate::helpers::read_bytes;
-use crate::{ColorDepth, Header};
+/// number of bytes from the current position to `end`
+fn bytes_until<R>(read: &mut R, end: u64) -> Result<u64>
+where
+    R: Seek,
+{
+    end.checked_sub(read.stream_position()?)
+        .ok_or(Error::Truncated {
+            location: Location::default(),
+        })
+}
 
 #[derive(Debug)]
 pub enum Cel {
@@ -23,41 +38,219 @@ pub enum Cel {
         height: u16,
         zlib_compressed_data: Vec<u8>,
     },
+    CompressedTilemap {
+        width_in_tiles: u16,
+        height_in_tiles: u16,
+        bits_per_tile: u16,
+        bitmask_tile_id: u32,
+        bitmask_x_flip: u32,
+        bitmask_y_flip: u32,
+        bitmask_diagonal_flip: u32,
+        zlib_compressed_data: Vec<u8>,
+    },
+}
+
+/// a single tile reference of a tilemap cel
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct Tile {
+    pub tile_id: u32,
+    pub x_flip: bool,
+    pub y_flip: bool,
+    pub diagonal_flip: bool,
+}
+
+/// decoded tilemap cel, tiles are stored row by row
+#[derive(Debug, Clone)]
+pub struct Tilemap {
+    pub width: u16,
+    pub height: u16,
+    pub tiles: Vec<Tile>,
+}
+
+impl Tilemap {
+    pub fn get(&self, x: u16, y: u16) -> Option<&Tile> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.tiles
+            .get(y as usize * self.width as usize + x as usize)
+    }
 }
 
 impl Cel {
     pub fn w(&self) -> Option<u16> {
         match &self {
-            Cel::CompressedImage { height, .. } => Some(*height),
-            Cel::RawCel { height, .. } => Some(*height),
+            Cel::CompressedImage { width, .. } => Some(*width),
+            Cel::RawCel { width, .. } => Some(*width),
             _ => None,
         }
     }
 
     pub fn h(&self) -> Option<u16> {
         match &self {
-            Cel::CompressedImage { width, .. } => Some(*width),
-            Cel::RawCel { width, .. } => Some(*width),
+            Cel::CompressedImage { height, .. } => Some(*height),
+            Cel::RawCel { height, .. } => Some(*height),
             _ => None,
         }
     }
 
-    pub fn pixels(&self, color_depth: &ColorDepth) -> Option<Pixels> {
-        match &self {
+    /// decompress the tile references of a tilemap cel
+    pub fn tilemap(&self) -> Result<Option<Tilemap>> {
+        let (
+            width,
+            height,
+            bits_per_tile,
+            bitmask_tile_id,
+            bitmask_x_flip,
+            bitmask_y_flip,
+            bitmask_diagonal_flip,
+            zlib_compressed_data,
+        ) = match &self {
+            Cel::CompressedTilemap {
+                width_in_tiles,
+                height_in_tiles,
+                bits_per_tile,
+                bitmask_tile_id,
+                bitmask_x_flip,
+                bitmask_y_flip,
+                bitmask_diagonal_flip,
+                zlib_compressed_data,
+            } => (
+                *width_in_tiles,
+                *height_in_tiles,
+                *bits_per_tile,
+                *bitmask_tile_id,
+                *bitmask_x_flip,
+                *bitmask_y_flip,
+                *bitmask_diagonal_flip,
+                zlib_compressed_data,
+            ),
+            _ => return Ok(None),
+        };
+
+        if ![8, 16, 32].contains(&bits_per_tile) {
+            return Err(Error::invalid_data(format!(
+                "Invalid Bits Per Tile {}",
+                bits_per_tile
+            )));
+        }
+        let bytes_per_tile = bits_per_tile as usize / 8;
+
+        let tile_count = width as usize * height as usize;
+        let data = zlib_decompress_bounded(
+            zlib_compressed_data,
+            tile_count.saturating_mul(bytes_per_tile),
+        )?;
+        let mut tiles = Vec::with_capacity(data.len() / bytes_per_tile);
+        let mut rdr = Cursor::new(data);
+        for _ in 0..tile_count {
+            let value = match bytes_per_tile {
+                1 => rdr.read_u8()? as u32,
+                2 => rdr.read_u16::<LittleEndian>()? as u32,
+                _ => rdr.read_u32::<LittleEndian>()?,
+            };
+            tiles.push(Tile {
+                tile_id: value & bitmask_tile_id,
+                x_flip: value & bitmask_x_flip != 0,
+                y_flip: value & bitmask_y_flip != 0,
+                diagonal_flip: value & bitmask_diagonal_flip != 0,
+            });
+        }
+
+        Ok(Some(Tilemap {
+            width,
+            height,
+            tiles,
+        }))
+    }
+
+    pub fn pixels(&self, color_depth: &ColorDepth) -> Result<Option<Pixels>> {
+        let pixels = match &self {
             Cel::CompressedImage {
+                width,
+                height,
                 zlib_compressed_data,
-                ..
             } => {
-                let mut d = ZlibDecoder::new(&zlib_compressed_data[..]);
-                let mut s = Vec::new();
-                d.read_to_end(&mut s).unwrap();
+                let max_size = Image::data_len(
+                    *width as u32,
+                    *height as u32,
+                    *color_depth,
+                );
+                let s =
+                    zlib_decompress_bounded(zlib_compressed_data, max_size)?;
                 let len = s.len() as u64;
                 let mut rdr = Cursor::new(s);
-                let pixels = CelChunk::read_pixels(&mut rdr, color_depth, len);
-                Some(pixels.unwrap().clone())
+                CelChunk::read_pixels(&mut rdr, color_depth, len)?
+            }
+            Cel::RawCel { pixels, .. } => pixels.clone(),
+            Cel::CompressedTilemap { .. } => return Ok(None),
+            // use `Sprite::resolve_cel` to get the cel it links to
+            Cel::LinkedCel { .. } => return Ok(None),
+        };
+        Ok(Some(pixels))
+    }
+
+    /// decoded image of a raw or compressed image cel, None for linked
+    /// and tilemap cels
+    pub fn image(&self, color_depth: &ColorDepth) -> Result<Option<Image>> {
+        let image = match self {
+            Cel::RawCel {
+                width,
+                height,
+                pixels,
+            } => Image::from_pixels(*width as u32, *height as u32, pixels)?,
+            Cel::CompressedImage {
+                width,
+                height,
+                zlib_compressed_data,
+            } => {
+                let (width, height) = (*width as u32, *height as u32);
+                let data = zlib_decompress_bounded(
+                    zlib_compressed_data,
+                    Image::data_len(width, height, *color_depth),
+                )?;
+                Image::from_data(width, height, *color_depth, data)?
+            }
+            Cel::LinkedCel { .. } | Cel::CompressedTilemap { .. } => {
+                return Ok(None)
+            }
+        };
+        Ok(Some(image))
+    }
+
+    /// compressed image cel with the pixels of a raw cel, using a zlib
+    /// `level` from 0 (fastest) to 9 (smallest), None for other cels
+    pub fn to_compressed(&self, level: u32) -> Result<Option<Cel>> {
+        match self {
+            Cel::RawCel {
+                width,
+                height,
+                pixels,
+            } => {
+                let mut data = Vec::new();
+                pixels.write(&mut data)?;
+                Ok(Some(Cel::CompressedImage {
+                    width: *width,
+                    height: *height,
+                    zlib_compressed_data: zlib_compress(&data, level)?,
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// raw cel with the pixels of a compressed image cel, None for other
+    /// cels
+    pub fn to_raw(&self, color_depth: &ColorDepth) -> Result<Option<Cel>> {
+        match self {
+            Cel::CompressedImage { width, height, .. } => {
+                Ok(self.image(color_depth)?.map(|image| Cel::RawCel {
+                    width: *width,
+                    height: *height,
+                    pixels: image.to_pixels(),
+                }))
             }
-            Cel::RawCel { pixels, .. } => Some(pixels.clone()),
-            _ => unimplemented!(),
+            _ => Ok(None),
         }
     }
 }
@@ -69,6 +262,7 @@ pub struct CelChunk {
     pub y_position: i16,
     pub opacity_level: u8,
     pub cel: Cel,
+    pub user_data: Option<UserDataChunk>,
 }
 
 impl CelChunk {
@@ -92,14 +286,42 @@ impl CelChunk {
             y_position: y,
             opacity_level: 255,
             cel,
+            user_data: None,
         }
     }
 
-    fn read_pixels<R>(
+    /// User Data chunk that annotates this cel
+    pub fn user_data(&self) -> Option<&UserDataChunk> {
+        self.user_data.as_ref()
+    }
+
+    /// decoded image of a raw or compressed image cel, None for linked
+    /// and tilemap cels
+    pub fn image(&self, color_depth: &ColorDepth) -> Result<Option<Image>> {
+        self.cel.image(color_depth)
+    }
+
+    /// compress a raw cel in place, see `Cel::to_compressed`
+    pub fn compress(&mut self, level: u32) -> Result<()> {
+        if let Some(cel) = self.cel.to_compressed(level)? {
+            self.cel = cel;
+        }
+        Ok(())
+    }
+
+    /// decompress a compressed image cel in place, see `Cel::to_raw`
+    pub fn decompress(&mut self, color_depth: &ColorDepth) -> Result<()> {
+        if let Some(cel) = self.cel.to_raw(color_depth)? {
+            self.cel = cel;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read_pixels<R>(
         read: &mut R,
         color_depth: &ColorDepth,
         pixels_size: u64,
-    ) -> io::Result<Pixels>
+    ) -> Result<Pixels>
     where
         R: Read + Seek,
     {
@@ -120,11 +342,29 @@ impl CelChunk {
         read: &mut R,
         chunk_data_size: u32,
         header: &Header,
-    ) -> io::Result<Self>
+    ) -> Result<Self>
+    where
+        R: Read + Seek,
+    {
+        CelChunk::from_read_with_limits(
+            read,
+            chunk_data_size,
+            header,
+            &Limits::unlimited(),
+        )
+    }
+
+    /// check the size of the cel against `limits` as soon as it's read
+    pub(crate) fn from_read_with_limits<R>(
+        read: &mut R,
+        chunk_data_size: u32,
+        header: &Header,
+        limits: &Limits,
+    ) -> Result<Self>
     where
         R: Read + Seek,
     {
-        let chunk_start = read.seek(SeekFrom::Current(0))?;
+        let chunk_end = read.stream_position()? + chunk_data_size as u64;
 
         let layer_index = read.read_u16::<LittleEndian>()?;
         let x_position = read.read_i16::<LittleEndian>()?;
@@ -136,8 +376,8 @@ impl CelChunk {
             0 => {
                 let width = read.read_u16::<LittleEndian>()?;
                 let height = read.read_u16::<LittleEndian>()?;
-                let pixels_size = chunk_start + chunk_data_size as u64
-                    - read.seek(SeekFrom::Current(0))?;
+                limits.check_cel(width, height, header.color_depth)?;
+                let pixels_size = bytes_until(read, chunk_end)?;
 
                 let pixels = CelChunk::read_pixels(
                     read,
@@ -156,9 +396,9 @@ impl CelChunk {
             2 => {
                 let width = read.read_u16::<LittleEndian>()?;
                 let height = read.read_u16::<LittleEndian>()?;
+                limits.check_cel(width, height, header.color_depth)?;
 
-                let data_size = chunk_start + chunk_data_size as u64
-                    - read.seek(SeekFrom::Current(0))?;
+                let data_size = bytes_until(read, chunk_end)?;
                 let zlib_compressed_data =
                     read_bytes(read, data_size as usize)?;
                 Cel::CompressedImage {
@@ -167,11 +407,40 @@ impl CelChunk {
                     zlib_compressed_data,
                 }
             }
+            3 => {
+                let width_in_tiles = read.read_u16::<LittleEndian>()?;
+                let height_in_tiles = read.read_u16::<LittleEndian>()?;
+                let bits_per_tile = read.read_u16::<LittleEndian>()?;
+                limits.check_tilemap(
+                    width_in_tiles,
+                    height_in_tiles,
+                    bits_per_tile,
+                )?;
+                let bitmask_tile_id = read.read_u32::<LittleEndian>()?;
+                let bitmask_x_flip = read.read_u32::<LittleEndian>()?;
+                let bitmask_y_flip = read.read_u32::<LittleEndian>()?;
+                let bitmask_diagonal_flip = read.read_u32::<LittleEndian>()?;
+                read.seek(SeekFrom::Current(10))?;
+
+                let data_size = bytes_until(read, chunk_end)?;
+                let zlib_compressed_data =
+                    read_bytes(read, data_size as usize)?;
+                Cel::CompressedTilemap {
+                    width_in_tiles,
+                    height_in_tiles,
+                    bits_per_tile,
+                    bitmask_tile_id,
+                    bitmask_x_flip,
+                    bitmask_y_flip,
+                    bitmask_diagonal_flip,
+                    zlib_compressed_data,
+                }
+            }
             _ => {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("Invalid Cel Type {}", cel_type),
-                ));
+                return Err(Error::invalid_enum_value(format!(
+                    "Invalid Cel Type {}",
+                    cel_type
+                )));
             }
         };
 
@@ -181,25 +450,53 @@ impl CelChunk {
             y_position,
             opacity_level,
             cel,
+            user_data: None,
         })
     }
 
-    pub fn write<W>(&self, wtr: &mut W) -> io::Result<()>
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
     where
-        W: Write + Seek,
+        W: Write,
+    {
+        self.write_cel(wtr, &self.cel)
+    }
+
+    /// write the chunk, compressing or decompressing its cel as set in
+    /// `options.cel_compression`
+    pub fn write_with_options<W>(
+        &self,
+        wtr: &mut W,
+        color_depth: &ColorDepth,
+        options: &WriteOptions,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        let cel = match options.cel_compression {
+            CelCompression::Keep => None,
+            CelCompression::Compress(level) => self.cel.to_compressed(level)?,
+            CelCompression::Decompress => self.cel.to_raw(color_depth)?,
+        };
+        self.write_cel(wtr, cel.as_ref().unwrap_or(&self.cel))
+    }
+
+    fn write_cel<W>(&self, wtr: &mut W, cel: &Cel) -> Result<()>
+    where
+        W: Write,
     {
         wtr.write_u16::<LittleEndian>(self.layer_index)?;
         wtr.write_i16::<LittleEndian>(self.x_position)?;
         wtr.write_i16::<LittleEndian>(self.y_position)?;
         wtr.write_u8(self.opacity_level)?;
-        let cel_type = match self.cel {
+        let cel_type = match cel {
             Cel::RawCel { .. } => 0,
             Cel::LinkedCel { .. } => 1,
             Cel::CompressedImage { .. } => 2,
+            Cel::CompressedTilemap { .. } => 3,
         };
         wtr.write_u16::<LittleEndian>(cel_type)?;
-        wtr.seek(SeekFrom::Current(7))?;
-        match &self.cel {
+        write_zeros(wtr, 7)?;
+        match cel {
             Cel::RawCel {
                 width,
                 height,
@@ -219,7 +516,27 @@ impl CelChunk {
             } => {
                 wtr.write_u16::<LittleEndian>(*width)?;
                 wtr.write_u16::<LittleEndian>(*height)?;
-                wtr.write(&zlib_compressed_data)?;
+                wtr.write_all(zlib_compressed_data)?;
+            }
+            Cel::CompressedTilemap {
+                width_in_tiles,
+                height_in_tiles,
+                bits_per_tile,
+                bitmask_tile_id,
+                bitmask_x_flip,
+                bitmask_y_flip,
+                bitmask_diagonal_flip,
+                zlib_compressed_data,
+            } => {
+                wtr.write_u16::<LittleEndian>(*width_in_tiles)?;
+                wtr.write_u16::<LittleEndian>(*height_in_tiles)?;
+                wtr.write_u16::<LittleEndian>(*bits_per_tile)?;
+                wtr.write_u32::<LittleEndian>(*bitmask_tile_id)?;
+                wtr.write_u32::<LittleEndian>(*bitmask_x_flip)?;
+                wtr.write_u32::<LittleEndian>(*bitmask_y_flip)?;
+                wtr.write_u32::<LittleEndian>(*bitmask_diagonal_flip)?;
+                write_zeros(wtr, 10)?;
+                wtr.write_all(zlib_compressed_data)?;
             }
         }
 
@@ -243,11 +560,25 @@ mod tests {
             153, 64, 142, 60, 41, 128, 158, 118, 161, 3, 0, 164, 249, 126, 89,
         ];
         let cel = Cel::CompressedImage {
-            width: 0,
-            height: 0,
-            zlib_compressed_data: data,
+            width: 27,
+            height: 19,
+            zlib_compressed_data: data.clone(),
         };
 
-        cel.pixels(&ColorDepth::RGBA);
+        match cel.pixels(&ColorDepth::RGBA).unwrap() {
+            Some(Pixels::RGBA(pixels)) => assert_eq!(pixels.len(), 27 * 19),
+            other => panic!("expected rgba pixels, got {:?}", other),
+        }
+
+        // inflating past the size of the cel is an error
+        let cel = Cel::CompressedImage {
+            width: 1,
+            height: 1,
+            zlib_compressed_data: data,
+        };
+        assert!(matches!(
+            cel.pixels(&ColorDepth::RGBA),
+            Err(Error::Decompression { .. })
+        ));
     }
 }
@@ -1,8 +1,11 @@
-use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 
 use bitflags::bitflags;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
+use crate::error::Result;
+use crate::helpers::write_zeros;
+
 bitflags! {
     pub struct Flags: u32 {
         const PreciseBounds = 1;
@@ -19,7 +22,7 @@ pub struct CelExtraChunk {
 }
 
 impl CelExtraChunk {
-    pub fn from_read<R>(read: &mut R) -> io::Result<Self>
+    pub fn from_read<R>(read: &mut R) -> Result<Self>
     where
         R: Read + Seek,
     {
@@ -39,16 +42,16 @@ impl CelExtraChunk {
         })
     }
 
-    pub fn write<W>(&self, wtr: &mut W) -> io::Result<()>
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
     where
-        W: Write + Seek,
+        W: Write,
     {
         wtr.write_u32::<LittleEndian>(self.flags.bits)?;
         wtr.write_f32::<LittleEndian>(self.precise_x_position)?;
         wtr.write_f32::<LittleEndian>(self.precise_y_position)?;
         wtr.write_f32::<LittleEndian>(self.width)?;
         wtr.write_f32::<LittleEndian>(self.height)?;
-        wtr.seek(SeekFrom::Current(16))?;
+        write_zeros(wtr, 16)?;
         Ok(())
     }
 }
@@ -1,10 +1,12 @@
-use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 
 use bitflags::bitflags;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use num_enum::CustomTryInto;
 
-use crate::helpers::read_bytes;
+use crate::error::{Error, Result};
+use crate::helpers::{block_size, read_bytes, write_zeros};
+use crate::{Fixed, SrgbTransform};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, CustomTryInto)]
 #[repr(u16)]
@@ -24,21 +26,23 @@ bitflags! {
 pub struct ColorProfileChunk {
     pub profile_type: ProfileType,
     pub flags: Flags,
-    pub fixed_gamma: f32,
+    /// gamma of an sRGB profile with the `SpecialFixedGamma` flag
+    pub fixed_gamma: Fixed,
+    /// ICC profile data when `profile_type` is `EmbeddedIccProfile`
     pub icc_profile: Vec<u8>,
 }
 
 impl ColorProfileChunk {
-    pub fn from_read<R>(read: &mut R) -> io::Result<Self>
+    pub fn from_read<R>(read: &mut R) -> Result<Self>
     where
         R: Read + Seek,
     {
         let profile_type = read
             .read_u16::<LittleEndian>()?
             .try_into_ProfileType()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            .map_err(Error::invalid_enum_value)?;
         let flags = Flags::from_bits_truncate(read.read_u16::<LittleEndian>()?);
-        let fixed_gamma = read.read_f32::<LittleEndian>()?;
+        let fixed_gamma = Fixed(read.read_i32::<LittleEndian>()?);
         read.seek(SeekFrom::Current(8))?;
 
         let icc_profile = if profile_type == ProfileType::EmbeddedIccProfile {
@@ -56,15 +60,40 @@ impl ColorProfileChunk {
         })
     }
 
-    pub fn write<W>(&self, wtr: &mut W) -> io::Result<()>
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
     where
-        W: Write + Seek,
+        W: Write,
     {
         wtr.write_u16::<LittleEndian>(self.profile_type as u16)?;
         wtr.write_u16::<LittleEndian>(self.flags.bits)?;
-        wtr.write_f32::<LittleEndian>(self.fixed_gamma)?;
-        wtr.seek(SeekFrom::Current(8))?;
-        wtr.write(&self.icc_profile)?;
+        wtr.write_i32::<LittleEndian>(self.fixed_gamma.0)?;
+        write_zeros(wtr, 8)?;
+        if self.profile_type == ProfileType::EmbeddedIccProfile {
+            wtr.write_u32::<LittleEndian>(block_size(
+                "ICC profile",
+                0,
+                self.icc_profile.len(),
+            )?)?;
+            wtr.write_all(&self.icc_profile)?;
+        }
         Ok(())
     }
+
+    /// conversion of the colors of this profile to sRGB, fails for ICC
+    /// profiles that aren't RGB matrix/TRC ones
+    pub fn srgb_transform(&self) -> Result<SrgbTransform> {
+        match self.profile_type {
+            ProfileType::None => Ok(SrgbTransform::identity()),
+            ProfileType::SRgb => {
+                if self.flags.contains(Flags::SpecialFixedGamma) {
+                    Ok(SrgbTransform::from_gamma(self.fixed_gamma.to_f64()))
+                } else {
+                    Ok(SrgbTransform::identity())
+                }
+            }
+            ProfileType::EmbeddedIccProfile => {
+                SrgbTransform::from_icc(&self.icc_profile)
+            }
+        }
+    }
 }
@@ -0,0 +1,114 @@
+/*
+DWORD       Number of entries
+BYTE[8]     Reserved (set to zero)
++ For each entry
+  DWORD     Entry ID (this ID is referenced by tilesets, palettes, or
+            extended properties)
+  BYTE      Type
+              0 - External palette
+              1 - External tileset
+              2 - Extension name for properties
+              3 - Extension name for tile management
+  BYTE[7]   Reserved (set to zero)
+  STRING    External file name or extension ID
+*/
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use num_enum::CustomTryInto;
+
+use crate::error::{Error, Result};
+use crate::helpers::{
+    bounded_capacity, read_string, write_string, write_zeros,
+};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, CustomTryInto)]
+#[repr(u8)]
+pub enum ExternalFileType {
+    Palette = 0,
+    Tileset = 1,
+    ExtensionProperties = 2,
+    ExtensionTileManagement = 3,
+}
+
+#[derive(Debug)]
+pub struct ExternalFile {
+    pub entry_id: u32,
+    pub file_type: ExternalFileType,
+    pub file_name: String,
+}
+
+#[derive(Debug)]
+pub struct ExternalFilesChunk {
+    pub number_of_entries: u32,
+    pub entries: Vec<ExternalFile>,
+}
+
+impl ExternalFilesChunk {
+    /// find the entry referenced by `entry_id`
+    pub fn entry(&self, entry_id: u32) -> Option<&ExternalFile> {
+        self.entries.iter().find(|e| e.entry_id == entry_id)
+    }
+
+    /// file name (or extension id) of the entry referenced by `entry_id`
+    pub fn file_name(&self, entry_id: u32) -> Option<&str> {
+        self.entry(entry_id).map(|e| &e.file_name[..])
+    }
+
+    pub fn from_read<R>(read: &mut R) -> Result<Self>
+    where
+        R: Read + Seek,
+    {
+        let number_of_entries = read.read_u32::<LittleEndian>()?;
+        read.seek(SeekFrom::Current(8))?;
+        // an entry is at least 14 bytes
+        let mut entries = Vec::with_capacity(bounded_capacity(
+            read,
+            number_of_entries as usize,
+            14,
+        )?);
+        for _ in 0..number_of_entries {
+            let entry_id = read.read_u32::<LittleEndian>()?;
+            let file_type = read
+                .read_u8()?
+                .try_into_ExternalFileType()
+                .map_err(Error::invalid_enum_value)?;
+            read.seek(SeekFrom::Current(7))?;
+            let file_name = read_string(read)?;
+
+            entries.push(ExternalFile {
+                entry_id,
+                file_type,
+                file_name,
+            });
+        }
+
+        Ok(Self {
+            number_of_entries,
+            entries,
+        })
+    }
+
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        if self.number_of_entries as usize != self.entries.len() {
+            return Err(Error::invalid_data(format!(
+                "Number of entries is {} but there are {} entries",
+                self.number_of_entries,
+                self.entries.len()
+            )));
+        }
+        wtr.write_u32::<LittleEndian>(self.number_of_entries)?;
+        write_zeros(wtr, 8)?;
+        for entry in &self.entries {
+            wtr.write_u32::<LittleEndian>(entry.entry_id)?;
+            wtr.write_u8(entry.file_type as u8)?;
+            write_zeros(wtr, 7)?;
+            write_string(wtr, &entry.file_name)?;
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,14 @@
-use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use num_enum::CustomTryInto;
 
 use crate::color::RGB256;
-use crate::helpers::{read_string, write_string};
+use crate::error::{Error, Result};
+use crate::helpers::{
+    bounded_capacity, read_string, write_string, write_zeros,
+};
+use crate::UserDataChunk;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, CustomTryInto)]
 #[repr(u8)]
@@ -21,6 +25,28 @@ pub struct Tag {
     pub loop_animation_direction: LoopAnimationDirection,
     pub tag_color: RGB256,
     pub tag_name: String,
+    pub user_data: Option<UserDataChunk>,
+}
+
+impl Tag {
+    /// User Data chunk that annotates this tag
+    pub fn user_data(&self) -> Option<&UserDataChunk> {
+        self.user_data.as_ref()
+    }
+
+    /// frames played by one loop of the tag, ping-pong goes back without
+    /// repeating the first and last frames, so the loop can restart
+    pub fn frame_sequence(&self) -> Vec<usize> {
+        let from = self.from_tag.min(self.to_tag) as usize;
+        let to = self.from_tag.max(self.to_tag) as usize;
+        match self.loop_animation_direction {
+            LoopAnimationDirection::Forward => (from..=to).collect(),
+            LoopAnimationDirection::Reverse => (from..=to).rev().collect(),
+            LoopAnimationDirection::PingPong => {
+                (from..=to).chain((from + 1..to).rev()).collect()
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -30,13 +56,18 @@ pub struct FrameTagsChunk {
 }
 
 impl FrameTagsChunk {
-    pub fn from_read<R>(read: &mut R) -> io::Result<Self>
+    pub fn from_read<R>(read: &mut R) -> Result<Self>
     where
         R: Read + Seek,
     {
         let number_of_tags = read.read_u16::<LittleEndian>()?;
         read.seek(SeekFrom::Current(8))?;
-        let mut tags = Vec::with_capacity(number_of_tags as usize);
+        // a tag is at least 19 bytes
+        let mut tags = Vec::with_capacity(bounded_capacity(
+            read,
+            number_of_tags as usize,
+            19,
+        )?);
 
         for _ in 0..number_of_tags {
             let from_tag = read.read_u16::<LittleEndian>()?;
@@ -44,7 +75,7 @@ impl FrameTagsChunk {
             let loop_animation_direction = read
                 .read_u8()?
                 .try_into_LoopAnimationDirection()
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+                .map_err(Error::invalid_enum_value)?;
             read.seek(SeekFrom::Current(8))?;
             let tag_color = RGB256 {
                 r: read.read_u8()?,
@@ -60,6 +91,7 @@ impl FrameTagsChunk {
                 loop_animation_direction,
                 tag_color,
                 tag_name,
+                user_data: None,
             });
         }
 
@@ -69,21 +101,21 @@ impl FrameTagsChunk {
         })
     }
 
-    pub fn write<W>(&self, wtr: &mut W) -> io::Result<()>
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
     where
-        W: Write + Seek,
+        W: Write,
     {
         wtr.write_u16::<LittleEndian>(self.number_of_tags)?;
-        wtr.seek(SeekFrom::Current(8))?;
+        write_zeros(wtr, 8)?;
         for tag in &self.tags {
             wtr.write_u16::<LittleEndian>(tag.from_tag)?;
             wtr.write_u16::<LittleEndian>(tag.to_tag)?;
             wtr.write_u8(tag.loop_animation_direction as u8)?;
-            wtr.seek(SeekFrom::Current(8))?;
+            write_zeros(wtr, 8)?;
             wtr.write_u8(tag.tag_color.r)?;
             wtr.write_u8(tag.tag_color.g)?;
             wtr.write_u8(tag.tag_color.b)?;
-            wtr.seek(SeekFrom::Current(1))?;
+            write_zeros(wtr, 1)?;
             write_string(wtr, &tag.tag_name)?;
         }
         Ok(())
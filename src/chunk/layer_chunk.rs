@@ -1,10 +1,12 @@
-use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 
 use bitflags::bitflags;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use num_enum::CustomTryInto;
 
-use crate::helpers::{read_string, write_string};
+use crate::error::{Error, Result};
+use crate::helpers::{read_string, write_string, write_zeros};
+use crate::UserDataChunk;
 
 bitflags! {
     pub struct Flags: u16 {
@@ -23,6 +25,7 @@ bitflags! {
 pub enum LayerType {
     Normal = 0,
     Group = 1,
+    Tilemap = 2,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, CustomTryInto)]
@@ -57,6 +60,8 @@ pub struct LayerChunk {
     pub blend_mode: BlendMode,
     pub opacity: u8,
     pub layer_name: String,
+    pub tileset_index: Option<u32>,
+    pub user_data: Option<UserDataChunk>,
 }
 
 impl LayerChunk {
@@ -72,9 +77,17 @@ impl LayerChunk {
             blend_mode: BlendMode::Normal,
             opacity: 255,
             layer_name: layer_name.to_owned(),
+            tileset_index: None,
+            user_data: None,
         }
     }
-    pub fn from_read<R>(read: &mut R) -> io::Result<Self>
+
+    /// User Data chunk that annotates this layer
+    pub fn user_data(&self) -> Option<&UserDataChunk> {
+        self.user_data.as_ref()
+    }
+
+    pub fn from_read<R>(read: &mut R) -> Result<Self>
     where
         R: Read + Seek,
     {
@@ -82,16 +95,21 @@ impl LayerChunk {
         let layer_type = read
             .read_u16::<LittleEndian>()?
             .try_into_LayerType()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            .map_err(Error::invalid_enum_value)?;
         let layer_child_level = read.read_u16::<LittleEndian>()?;
         read.seek(SeekFrom::Current(2 + 2))?;
         let blend_mode = read
             .read_u16::<LittleEndian>()?
             .try_into_BlendMode()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            .map_err(Error::invalid_enum_value)?;
         let opacity = read.read_u8()?;
         read.seek(SeekFrom::Current(3))?;
         let layer_name = read_string(read)?;
+        let tileset_index = if layer_type == LayerType::Tilemap {
+            Some(read.read_u32::<LittleEndian>()?)
+        } else {
+            None
+        };
 
         Ok(Self {
             flags,
@@ -100,22 +118,36 @@ impl LayerChunk {
             blend_mode,
             opacity,
             layer_name,
+            tileset_index,
+            user_data: None,
         })
     }
 
-    pub fn write<W>(&self, wtr: &mut W) -> io::Result<()>
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
     where
-        W: Write + Seek,
+        W: Write,
     {
         wtr.write_u16::<LittleEndian>(self.flags.bits)?;
         wtr.write_u16::<LittleEndian>(self.layer_type as u16)?;
         wtr.write_u16::<LittleEndian>(self.layer_child_level)?;
-        wtr.seek(SeekFrom::Current(2 + 2))?;
+        write_zeros(wtr, 2 + 2)?;
         wtr.write_u16::<LittleEndian>(self.blend_mode as u16)?;
         wtr.write_u8(self.opacity)?;
 
-        wtr.seek(SeekFrom::Current(3))?;
+        write_zeros(wtr, 3)?;
         write_string(wtr, &self.layer_name)?;
+        if self.layer_type == LayerType::Tilemap {
+            match self.tileset_index {
+                None => {
+                    return Err(Error::invalid_data(
+                        "Layer type is `Tilemap` but `tileset_index` is None",
+                    ));
+                }
+                Some(tileset_index) => {
+                    wtr.write_u32::<LittleEndian>(tileset_index)?;
+                }
+            }
+        }
 
         Ok(())
     }
@@ -1,8 +1,9 @@
-use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
-use crate::helpers::{read_bytes, read_string, write_string};
+use crate::error::Result;
+use crate::helpers::{read_bytes, read_string, write_string, write_zeros};
 
 #[derive(Debug)]
 pub struct MaskChunk {
@@ -15,7 +16,7 @@ pub struct MaskChunk {
 }
 
 impl MaskChunk {
-    pub fn from_read<R>(read: &mut R) -> io::Result<Self>
+    pub fn from_read<R>(read: &mut R) -> Result<Self>
     where
         R: Read + Seek,
     {
@@ -25,8 +26,8 @@ impl MaskChunk {
         let height = read.read_u16::<LittleEndian>()?;
         read.seek(SeekFrom::Current(8))?;
         let mask_name = read_string(read)?;
-        let bitmap_data =
-            read_bytes(read, (height * (width + 7) / 8) as usize)?;
+        let row_size = (width as usize).div_ceil(8);
+        let bitmap_data = read_bytes(read, height as usize * row_size)?;
 
         Ok(Self {
             x_position,
@@ -38,17 +39,17 @@ impl MaskChunk {
         })
     }
 
-    pub fn write<W>(&self, wtr: &mut W) -> io::Result<()>
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
     where
-        W: Write + Seek,
+        W: Write,
     {
         wtr.write_i16::<LittleEndian>(self.x_position)?;
         wtr.write_i16::<LittleEndian>(self.y_position)?;
         wtr.write_u16::<LittleEndian>(self.width)?;
         wtr.write_u16::<LittleEndian>(self.height)?;
-        wtr.seek(SeekFrom::Current(8))?;
+        write_zeros(wtr, 8)?;
         write_string(wtr, &self.mask_name)?;
-        wtr.write(&self.bitmap_data)?;
+        wtr.write_all(&self.bitmap_data)?;
         Ok(())
     }
 }
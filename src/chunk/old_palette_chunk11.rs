@@ -1,8 +1,12 @@
-use std::io::{self, Read, Seek, Write};
+use std::io::{Read, Write};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::color::RGB64;
+use crate::error::Result;
+use crate::old_palette_chunk4::{
+    write_number_of_colors, write_number_of_packets,
+};
 
 #[derive(Debug)]
 pub struct Packet {
@@ -17,7 +21,7 @@ pub struct OldPaletteChunk11 {
 }
 
 impl OldPaletteChunk11 {
-    pub fn from_read<R>(read: &mut R) -> io::Result<Self>
+    pub fn from_read<R>(read: &mut R) -> Result<Self>
     where
         R: Read,
     {
@@ -54,13 +58,18 @@ impl OldPaletteChunk11 {
         })
     }
 
-    pub fn write<W>(&self, wtr: &mut W) -> io::Result<()>
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
     where
-        W: Write + Seek,
+        W: Write,
     {
-        wtr.write_u16::<LittleEndian>(self.number_of_packets)?;
+        write_number_of_packets(
+            wtr,
+            self.number_of_packets,
+            self.packets.len(),
+        )?;
         for packet in &self.packets {
             wtr.write_u8(packet.palette_entries_to_skip)?;
+            write_number_of_colors(wtr, packet.colors.len())?;
             for color in &packet.colors {
                 wtr.write_u8(color.r)?;
                 wtr.write_u8(color.g)?;
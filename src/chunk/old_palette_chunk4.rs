@@ -1,8 +1,9 @@
-use std::io::{self, Read, Seek, Write};
+use std::io::{Read, Write};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::color::RGB256;
+use crate::error::{Error, Result};
 
 #[derive(Debug)]
 pub struct Packet {
@@ -16,8 +17,49 @@ pub struct OldPaletteChunk4 {
     pub packets: Vec<Packet>,
 }
 
+/// check the number of packets and write it, shared with the 0x0011 chunk
+pub(crate) fn write_number_of_packets<W>(
+    wtr: &mut W,
+    number_of_packets: u16,
+    packets: usize,
+) -> Result<()>
+where
+    W: Write,
+{
+    if number_of_packets as usize != packets {
+        return Err(Error::invalid_data(format!(
+            "Number of packets is {} but there are {} packets",
+            number_of_packets, packets
+        )));
+    }
+    wtr.write_u16::<LittleEndian>(number_of_packets)?;
+    Ok(())
+}
+
+/// write the number of colors of a packet, 0 stands for 256 colors
+pub(crate) fn write_number_of_colors<W>(
+    wtr: &mut W,
+    colors: usize,
+) -> Result<()>
+where
+    W: Write,
+{
+    let number_of_colors = match colors {
+        256 => 0,
+        1..=255 => colors as u8,
+        _ => {
+            return Err(Error::invalid_data(format!(
+                "Old palette packet has {} colors, not 1 to 256",
+                colors
+            )))
+        }
+    };
+    wtr.write_u8(number_of_colors)?;
+    Ok(())
+}
+
 impl OldPaletteChunk4 {
-    pub fn from_read<R>(read: &mut R) -> io::Result<Self>
+    pub fn from_read<R>(read: &mut R) -> Result<Self>
     where
         R: Read,
     {
@@ -54,14 +96,18 @@ impl OldPaletteChunk4 {
         })
     }
 
-    pub fn write<W>(&self, wtr: &mut W) -> io::Result<()>
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
     where
-        W: Write + Seek,
+        W: Write,
     {
-        wtr.write_u16::<LittleEndian>(self.number_of_packets)?;
+        write_number_of_packets(
+            wtr,
+            self.number_of_packets,
+            self.packets.len(),
+        )?;
         for packet in &self.packets {
             wtr.write_u8(packet.palette_entries_to_skip)?;
-            wtr.write_u8(packet.colors.len() as u8)?;
+            write_number_of_colors(wtr, packet.colors.len())?;
             for color in &packet.colors {
                 wtr.write_u8(color.r)?;
                 wtr.write_u8(color.g)?;
@@ -14,13 +14,17 @@ BYTE[8]     For future (set to zero)
     STRING  Color name
     */
 
-use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 
 use bitflags::bitflags;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::color::RGBA256;
-use crate::helpers::{read_string, write_string};
+use crate::error::{Error, Location, Result};
+use crate::helpers::{
+    bounded_capacity, read_string, write_string, write_zeros,
+};
+use crate::{Limits, UserDataChunk};
 
 bitflags! {
     pub struct Flags: u16 {
@@ -41,19 +45,65 @@ pub struct PaletteChunk {
     pub first_color_index_to_change: u32,
     pub last_color_index_to_change: u32,
     pub palette_entries: Vec<PaletteEntry>,
+    /// in the first frame, this is the user data of the sprite
+    pub user_data: Option<UserDataChunk>,
 }
 
 impl PaletteChunk {
-    pub fn from_read<R>(read: &mut R) -> io::Result<Self>
+    /// User Data chunk that annotates this palette
+    pub fn user_data(&self) -> Option<&UserDataChunk> {
+        self.user_data.as_ref()
+    }
+
+    pub fn from_read<R>(read: &mut R, chunk_data_size: u32) -> Result<Self>
+    where
+        R: Read + Seek,
+    {
+        PaletteChunk::from_read_with_limits(
+            read,
+            chunk_data_size,
+            &Limits::unlimited(),
+        )
+    }
+
+    /// check the palette size and its number of entries against `limits`
+    /// before reading the entries
+    pub(crate) fn from_read_with_limits<R>(
+        read: &mut R,
+        chunk_data_size: u32,
+        limits: &Limits,
+    ) -> Result<Self>
     where
         R: Read + Seek,
     {
+        let start = read.stream_position()?;
+        let end = start + chunk_data_size as u64;
         let new_palette_size = read.read_u32::<LittleEndian>()?;
         let first_color_index_to_change = read.read_u32::<LittleEndian>()?;
         let last_color_index_to_change = read.read_u32::<LittleEndian>()?;
         read.seek(SeekFrom::Current(8))?;
-        let mut palette_entries = Vec::with_capacity(new_palette_size as usize);
-        for _ in 0..new_palette_size {
+        if last_color_index_to_change < first_color_index_to_change {
+            return Err(Error::invalid_data(format!(
+                "Last color index {} is before the first color index {}",
+                last_color_index_to_change, first_color_index_to_change
+            )));
+        }
+        let number_of_entries =
+            (last_color_index_to_change - first_color_index_to_change) as u64
+                + 1;
+        limits.check_palette(new_palette_size, number_of_entries)?;
+        // an entry is at least 6 bytes
+        let mut palette_entries = Vec::with_capacity(bounded_capacity(
+            read,
+            number_of_entries as usize,
+            6,
+        )?);
+        for _ in 0..number_of_entries {
+            if read.stream_position()? >= end {
+                return Err(Error::Truncated {
+                    location: Location::default(),
+                });
+            }
             let flags =
                 Flags::from_bits_truncate(read.read_u16::<LittleEndian>()?);
             let color = RGBA256 {
@@ -81,22 +131,34 @@ impl PaletteChunk {
             first_color_index_to_change,
             last_color_index_to_change,
             palette_entries,
+            user_data: None,
         })
     }
 
-    pub fn write<W>(&self, wtr: &mut W) -> io::Result<()>
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
     where
-        W: Write + Seek,
+        W: Write,
     {
-        wtr.write_u32::<LittleEndian>(self.palette_entries.len() as u32)?;
+        let number_of_entries = self
+            .last_color_index_to_change
+            .checked_sub(self.first_color_index_to_change)
+            .map(|n| n as u64 + 1);
+        if number_of_entries != Some(self.palette_entries.len() as u64) {
+            return Err(Error::invalid_data(format!(
+                "Color indexes {} to {} don't match the {} palette entries",
+                self.first_color_index_to_change,
+                self.last_color_index_to_change,
+                self.palette_entries.len()
+            )));
+        }
+        wtr.write_u32::<LittleEndian>(self.new_palette_size)?;
         wtr.write_u32::<LittleEndian>(self.first_color_index_to_change)?;
         wtr.write_u32::<LittleEndian>(self.last_color_index_to_change)?;
-        wtr.seek(SeekFrom::Current(8))?;
+        write_zeros(wtr, 8)?;
         for pal in &self.palette_entries {
             if pal.flags.contains(Flags::HasName) && pal.color_name.is_none() {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "Flag HasName is 1 but col_name is None".to_owned(),
+                return Err(Error::invalid_data(
+                    "Flag HasName is 1 but col_name is None",
                 ));
             }
             wtr.write_u16::<LittleEndian>(pal.flags.bits)?;
@@ -105,7 +167,7 @@ impl PaletteChunk {
             wtr.write_u8(pal.color.b)?;
             wtr.write_u8(pal.color.a)?;
             if let Some(name) = &pal.color_name {
-                write_string(wtr, &name)?
+                write_string(wtr, name)?
             }
         }
         Ok(())
@@ -1,10 +1,12 @@
-use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::error::Result;
 
 #[derive(Debug)]
 pub struct PathChunk {}
 
 impl PathChunk {
-    pub fn from_read<R>(read: &mut R, chunk_data_size: u32) -> io::Result<Self>
+    pub fn from_read<R>(read: &mut R, chunk_data_size: u32) -> Result<Self>
     where
         R: Read + Seek,
     {
@@ -12,9 +14,9 @@ impl PathChunk {
         Ok(Self {})
     }
 
-    pub fn write<W>(&self, _wtr: &mut W) -> io::Result<()>
+    pub fn write<W>(&self, _wtr: &mut W) -> Result<()>
     where
-        W: Write + Seek,
+        W: Write,
     {
         Ok(())
     }
@@ -1,9 +1,13 @@
-use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 
 use bitflags::bitflags;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
-use crate::helpers::{read_string, write_string};
+use crate::error::{Error, Result};
+use crate::helpers::{
+    bounded_capacity, read_string, write_string, write_zeros,
+};
+use crate::UserDataChunk;
 
 bitflags! {
     pub struct Flags: u32 {
@@ -43,10 +47,16 @@ pub struct SliceChunk {
     pub flags: Flags,
     pub name: String,
     pub keys: Vec<SliceKey>,
+    pub user_data: Option<UserDataChunk>,
 }
 
 impl SliceChunk {
-    pub fn from_read<R>(read: &mut R) -> io::Result<Self>
+    /// User Data chunk that annotates this slice
+    pub fn user_data(&self) -> Option<&UserDataChunk> {
+        self.user_data.as_ref()
+    }
+
+    pub fn from_read<R>(read: &mut R) -> Result<Self>
     where
         R: Read + Seek,
     {
@@ -54,7 +64,12 @@ impl SliceChunk {
         let flags = Flags::from_bits_truncate(read.read_u32::<LittleEndian>()?);
         read.seek(SeekFrom::Current(4))?;
         let name = read_string(read)?;
-        let mut keys = Vec::with_capacity(number_of_slice_keys as usize);
+        // a slice key is at least 20 bytes
+        let mut keys = Vec::with_capacity(bounded_capacity(
+            read,
+            number_of_slice_keys as usize,
+            20,
+        )?);
         for _ in 0..number_of_slice_keys {
             let frame_number = read.read_u32::<LittleEndian>()?;
             let x_origin = read.read_i32::<LittleEndian>()?;
@@ -97,16 +112,17 @@ impl SliceChunk {
             flags,
             name,
             keys,
+            user_data: None,
         })
     }
 
-    pub fn write<W>(&self, wtr: &mut W) -> io::Result<()>
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
     where
-        W: Write + Seek,
+        W: Write,
     {
         wtr.write_u32::<LittleEndian>(self.number_of_slice_keys)?;
         wtr.write_u32::<LittleEndian>(self.flags.bits)?;
-        wtr.seek(SeekFrom::Current(4))?;
+        write_zeros(wtr, 4)?;
         write_string(wtr, &self.name)?;
         for key in &self.keys {
             wtr.write_u32::<LittleEndian>(key.frame_number)?;
@@ -117,10 +133,7 @@ impl SliceChunk {
             if self.flags.contains(Flags::IsNinePatchesSlice) {
                 match key.nine_patches_info {
                     None => {
-                        return Err(io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            "Flag `IsNinePatchesSlice` is 1 but `nine_patches_info` is None"
-                                .to_owned(),
+                        return Err(Error::invalid_data("Flag `IsNinePatchesSlice` is 1 but `nine_patches_info` is None",
                         ));
                     }
                     Some(NinePatchesInfo {
@@ -139,9 +152,7 @@ impl SliceChunk {
             if self.flags.contains(Flags::HasPivotInformation) {
                 match key.pivot_info {
                     None => {
-                        return Err(io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            "Flag `HasPivotInformation` is 1 but `pivot_info` is None".to_owned(),
+                        return Err(Error::invalid_data("Flag `HasPivotInformation` is 1 but `pivot_info` is None",
                         ));
                     }
                     Some(PivotInfo {
@@ -0,0 +1,256 @@
+/*
+DWORD       Tileset ID
+DWORD       Tileset flags
+              1 - Include link to external file
+              2 - Include tiles inside this file
+              4 - Tilemaps using this tileset use tile ID=0 as empty tile
+              8 - Try to match modified tiles with their X flipped version
+              16 - Try to match modified tiles with their Y flipped version
+              32 - Try to match modified tiles with their D flipped version
+DWORD       Number of tiles
+WORD        Tile Width
+WORD        Tile Height
+SHORT       Base Index
+BYTE[14]    Reserved
+STRING      Name of the tileset
++ If flag 1 is set
+  DWORD     ID of the external file
+  DWORD     Tileset ID in the external file
++ If flag 2 is set
+  DWORD     Data length (compressed)
+  PIXEL[]   Compressed Tileset image:
+              (Tile Width) x (Tile Height x Number of Tiles)
+*/
+
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use bitflags::bitflags;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::color::Pixels;
+use crate::error::{Error, Result};
+use crate::helpers::{
+    block_size, read_bytes, read_string, write_string, write_zeros,
+    zlib_decompress_bounded,
+};
+use crate::image::Image;
+use crate::{
+    Aseprite, CelChunk, ColorDepth, ExternalFile, Limits, UserDataChunk,
+};
+
+bitflags! {
+    pub struct Flags: u32 {
+        const LinkToExternalFile = 1;
+        const TilesInsideFile = 2;
+        const EmptyTileIsZero = 4;
+        const MatchXFlip = 8;
+        const MatchYFlip = 16;
+        const MatchDiagonalFlip = 32;
+    }
+}
+
+#[derive(Debug)]
+pub struct ExternalTileset {
+    pub external_file_id: u32,
+    pub tileset_id: u32,
+}
+
+#[derive(Debug)]
+pub struct TilesetChunk {
+    pub tileset_id: u32,
+    pub flags: Flags,
+    pub number_of_tiles: u32,
+    pub tile_width: u16,
+    pub tile_height: u16,
+    pub base_index: i16,
+    pub name: String,
+    pub external_tileset: Option<ExternalTileset>,
+    pub zlib_compressed_data: Option<Vec<u8>>,
+    pub user_data: Option<UserDataChunk>,
+    /// user data of each tile, in tile order
+    pub tiles_user_data: Vec<UserDataChunk>,
+}
+
+impl TilesetChunk {
+    /// User Data chunk that annotates this tileset
+    pub fn user_data(&self) -> Option<&UserDataChunk> {
+        self.user_data.as_ref()
+    }
+
+    /// User Data chunk that annotates the tile at `tile_index`
+    pub fn tile_user_data(&self, tile_index: usize) -> Option<&UserDataChunk> {
+        self.tiles_user_data.get(tile_index)
+    }
+
+    /// width of the tileset image, tiles are stacked vertically
+    pub fn image_width(&self) -> u32 {
+        self.tile_width as u32
+    }
+
+    /// height of the tileset image, tiles are stacked vertically
+    pub fn image_height(&self) -> u32 {
+        (self.tile_height as u32).saturating_mul(self.number_of_tiles)
+    }
+
+    /// resolve the External Files entry this tileset is linked to
+    pub fn external_file<'a>(
+        &self,
+        ase: &'a Aseprite,
+    ) -> Option<&'a ExternalFile> {
+        let external_tileset = self.external_tileset.as_ref()?;
+        ase.external_file(external_tileset.external_file_id)
+    }
+
+    /// decompress the tileset image stored inside this file, if any
+    pub fn pixels(&self, color_depth: &ColorDepth) -> Result<Option<Pixels>> {
+        let zlib_compressed_data = match &self.zlib_compressed_data {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+
+        let max_size = Image::data_len(
+            self.image_width(),
+            self.image_height(),
+            *color_depth,
+        );
+        let s = zlib_decompress_bounded(zlib_compressed_data, max_size)?;
+        let len = s.len() as u64;
+        let mut rdr = Cursor::new(s);
+        let pixels = CelChunk::read_pixels(&mut rdr, color_depth, len)?;
+        Ok(Some(pixels))
+    }
+
+    /// decompress the tileset image stored inside this file, if any
+    pub fn image(&self, color_depth: &ColorDepth) -> Result<Option<Image>> {
+        let zlib_compressed_data = match &self.zlib_compressed_data {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+
+        let (width, height) = (self.image_width(), self.image_height());
+        let data = zlib_decompress_bounded(
+            zlib_compressed_data,
+            Image::data_len(width, height, *color_depth),
+        )?;
+        let image = Image::from_data(width, height, *color_depth, data)?;
+        Ok(Some(image))
+    }
+
+    pub fn from_read<R>(read: &mut R) -> Result<Self>
+    where
+        R: Read + Seek,
+    {
+        TilesetChunk::from_read_with_limits(
+            read,
+            ColorDepth::RGBA,
+            &Limits::unlimited(),
+        )
+    }
+
+    /// check the size of the tileset against `limits` as soon as it's read,
+    /// `color_depth` is the color depth of the sprite
+    pub(crate) fn from_read_with_limits<R>(
+        read: &mut R,
+        color_depth: ColorDepth,
+        limits: &Limits,
+    ) -> Result<Self>
+    where
+        R: Read + Seek,
+    {
+        let tileset_id = read.read_u32::<LittleEndian>()?;
+        // keep the flags this version doesn't know so they're written back
+        let flags = Flags {
+            bits: read.read_u32::<LittleEndian>()?,
+        };
+        let number_of_tiles = read.read_u32::<LittleEndian>()?;
+        let tile_width = read.read_u16::<LittleEndian>()?;
+        let tile_height = read.read_u16::<LittleEndian>()?;
+        limits.check_tileset(
+            tile_width,
+            tile_height,
+            number_of_tiles,
+            color_depth,
+        )?;
+        let base_index = read.read_i16::<LittleEndian>()?;
+        read.seek(SeekFrom::Current(14))?;
+        let name = read_string(read)?;
+
+        let external_tileset = if flags.contains(Flags::LinkToExternalFile) {
+            Some(ExternalTileset {
+                external_file_id: read.read_u32::<LittleEndian>()?,
+                tileset_id: read.read_u32::<LittleEndian>()?,
+            })
+        } else {
+            None
+        };
+
+        let zlib_compressed_data = if flags.contains(Flags::TilesInsideFile) {
+            let data_length = read.read_u32::<LittleEndian>()? as usize;
+            Some(read_bytes(read, data_length)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            tileset_id,
+            flags,
+            number_of_tiles,
+            tile_width,
+            tile_height,
+            base_index,
+            name,
+            external_tileset,
+            zlib_compressed_data,
+            user_data: None,
+            tiles_user_data: Vec::new(),
+        })
+    }
+
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        wtr.write_u32::<LittleEndian>(self.tileset_id)?;
+        wtr.write_u32::<LittleEndian>(self.flags.bits)?;
+        wtr.write_u32::<LittleEndian>(self.number_of_tiles)?;
+        wtr.write_u16::<LittleEndian>(self.tile_width)?;
+        wtr.write_u16::<LittleEndian>(self.tile_height)?;
+        wtr.write_i16::<LittleEndian>(self.base_index)?;
+        write_zeros(wtr, 14)?;
+        write_string(wtr, &self.name)?;
+
+        if self.flags.contains(Flags::LinkToExternalFile) {
+            match &self.external_tileset {
+                None => {
+                    return Err(Error::invalid_data("Flag `LinkToExternalFile` is 1 but `external_tileset` is None",
+                    ));
+                }
+                Some(external_tileset) => {
+                    wtr.write_u32::<LittleEndian>(
+                        external_tileset.external_file_id,
+                    )?;
+                    wtr.write_u32::<LittleEndian>(external_tileset.tileset_id)?;
+                }
+            }
+        }
+
+        if self.flags.contains(Flags::TilesInsideFile) {
+            match &self.zlib_compressed_data {
+                None => {
+                    return Err(Error::invalid_data("Flag `TilesInsideFile` is 1 but `zlib_compressed_data` is None",
+                    ));
+                }
+                Some(zlib_compressed_data) => {
+                    wtr.write_u32::<LittleEndian>(block_size(
+                        "Tileset data",
+                        0,
+                        zlib_compressed_data.len(),
+                    )?)?;
+                    wtr.write_all(zlib_compressed_data)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
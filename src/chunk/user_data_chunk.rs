@@ -1,27 +1,394 @@
-use std::io::{self, Read, Write};
+use std::io::{Read, Write};
 
 use bitflags::bitflags;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use num_enum::CustomTryInto;
 
 use crate::color::RGBA256;
-use crate::helpers::{read_string, write_string};
+use crate::error::{Error, Result};
+use crate::helpers::{
+    block_size, count_field, read_bytes, read_string, write_string,
+};
+use crate::{Aseprite, ExternalFile, Fixed};
+
+/// deepest nesting of vectors and maps in properties, deeper values are
+/// rejected instead of overflowing the stack
+const MAX_PROPERTY_DEPTH: usize = 64;
 
 bitflags! {
     pub struct Flags: u32 {
         const HasText = 1;
         const HasColor = 2;
+        const HasProperties = 4;
     }
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq, CustomTryInto)]
+#[repr(u16)]
+pub enum PropertyType {
+    Bool = 0x0001,
+    Int8 = 0x0002,
+    UInt8 = 0x0003,
+    Int16 = 0x0004,
+    UInt16 = 0x0005,
+    Int32 = 0x0006,
+    UInt32 = 0x0007,
+    Int64 = 0x0008,
+    UInt64 = 0x0009,
+    Fixed = 0x000A,
+    Float = 0x000B,
+    Double = 0x000C,
+    String = 0x000D,
+    Point = 0x000E,
+    Size = 0x000F,
+    Rect = 0x0010,
+    Vector = 0x0011,
+    Map = 0x0012,
+    Uuid = 0x0013,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+    Bool(bool),
+    Int8(i8),
+    UInt8(u8),
+    Int16(i16),
+    UInt16(u16),
+    Int32(i32),
+    UInt32(u32),
+    Int64(i64),
+    UInt64(u64),
+    Fixed(Fixed),
+    Float(f32),
+    Double(f64),
+    String(String),
+    Point {
+        x: i32,
+        y: i32,
+    },
+    Size {
+        width: i32,
+        height: i32,
+    },
+    Rect {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    },
+    /// `element_type` is None when the elements are not all of the same
+    /// type, in which case each element is stored along with its type
+    Vector {
+        element_type: Option<PropertyType>,
+        elements: Vec<PropertyValue>,
+    },
+    Map(Vec<Property>),
+    Uuid([u8; 16]),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Property {
+    pub name: String,
+    pub value: PropertyValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertiesMap {
+    /// 0 for user properties, otherwise an External Files entry id
+    pub key: u32,
+    pub properties: Vec<Property>,
+}
+
 #[derive(Debug)]
 pub struct UserDataChunk {
     pub flags: Flags,
     pub text: Option<String>,
     pub color: Option<RGBA256>,
+    pub properties_maps: Vec<PropertiesMap>,
+}
+
+impl PropertyValue {
+    pub fn property_type(&self) -> PropertyType {
+        use self::PropertyValue::*;
+        match self {
+            Bool(_) => PropertyType::Bool,
+            Int8(_) => PropertyType::Int8,
+            UInt8(_) => PropertyType::UInt8,
+            Int16(_) => PropertyType::Int16,
+            UInt16(_) => PropertyType::UInt16,
+            Int32(_) => PropertyType::Int32,
+            UInt32(_) => PropertyType::UInt32,
+            Int64(_) => PropertyType::Int64,
+            UInt64(_) => PropertyType::UInt64,
+            Fixed(_) => PropertyType::Fixed,
+            Float(_) => PropertyType::Float,
+            Double(_) => PropertyType::Double,
+            String(_) => PropertyType::String,
+            Point { .. } => PropertyType::Point,
+            Size { .. } => PropertyType::Size,
+            Rect { .. } => PropertyType::Rect,
+            Vector { .. } => PropertyType::Vector,
+            Map(_) => PropertyType::Map,
+            Uuid(_) => PropertyType::Uuid,
+        }
+    }
+
+    /// `depth` is the number of vectors and maps this value is nested in
+    fn from_read<R>(
+        read: &mut R,
+        property_type: PropertyType,
+        depth: usize,
+    ) -> Result<Self>
+    where
+        R: Read,
+    {
+        if depth > MAX_PROPERTY_DEPTH {
+            return Err(Error::invalid_data(format!(
+                "Properties are nested more than {} levels deep",
+                MAX_PROPERTY_DEPTH
+            )));
+        }
+        let value = match property_type {
+            PropertyType::Bool => PropertyValue::Bool(read.read_u8()? != 0),
+            PropertyType::Int8 => PropertyValue::Int8(read.read_i8()?),
+            PropertyType::UInt8 => PropertyValue::UInt8(read.read_u8()?),
+            PropertyType::Int16 => {
+                PropertyValue::Int16(read.read_i16::<LittleEndian>()?)
+            }
+            PropertyType::UInt16 => {
+                PropertyValue::UInt16(read.read_u16::<LittleEndian>()?)
+            }
+            PropertyType::Int32 => {
+                PropertyValue::Int32(read.read_i32::<LittleEndian>()?)
+            }
+            PropertyType::UInt32 => {
+                PropertyValue::UInt32(read.read_u32::<LittleEndian>()?)
+            }
+            PropertyType::Int64 => {
+                PropertyValue::Int64(read.read_i64::<LittleEndian>()?)
+            }
+            PropertyType::UInt64 => {
+                PropertyValue::UInt64(read.read_u64::<LittleEndian>()?)
+            }
+            PropertyType::Fixed => {
+                PropertyValue::Fixed(Fixed(read.read_i32::<LittleEndian>()?))
+            }
+            PropertyType::Float => {
+                PropertyValue::Float(read.read_f32::<LittleEndian>()?)
+            }
+            PropertyType::Double => {
+                PropertyValue::Double(read.read_f64::<LittleEndian>()?)
+            }
+            PropertyType::String => PropertyValue::String(read_string(read)?),
+            PropertyType::Point => PropertyValue::Point {
+                x: read.read_i32::<LittleEndian>()?,
+                y: read.read_i32::<LittleEndian>()?,
+            },
+            PropertyType::Size => PropertyValue::Size {
+                width: read.read_i32::<LittleEndian>()?,
+                height: read.read_i32::<LittleEndian>()?,
+            },
+            PropertyType::Rect => PropertyValue::Rect {
+                x: read.read_i32::<LittleEndian>()?,
+                y: read.read_i32::<LittleEndian>()?,
+                width: read.read_i32::<LittleEndian>()?,
+                height: read.read_i32::<LittleEndian>()?,
+            },
+            PropertyType::Vector => {
+                let number_of_elements = read.read_u32::<LittleEndian>()?;
+                let element_type = match read.read_u16::<LittleEndian>()? {
+                    0 => None,
+                    t => Some(
+                        t.try_into_PropertyType()
+                            .map_err(Error::invalid_enum_value)?,
+                    ),
+                };
+                let mut elements = Vec::new();
+                for _ in 0..number_of_elements {
+                    let t = match element_type {
+                        Some(t) => t,
+                        None => read_property_type(read)?,
+                    };
+                    elements.push(PropertyValue::from_read(
+                        read,
+                        t,
+                        depth + 1,
+                    )?);
+                }
+                PropertyValue::Vector {
+                    element_type,
+                    elements,
+                }
+            }
+            PropertyType::Map => {
+                PropertyValue::Map(read_properties(read, depth + 1)?)
+            }
+            PropertyType::Uuid => {
+                let mut uuid = [0; 16];
+                read.read_exact(&mut uuid)?;
+                PropertyValue::Uuid(uuid)
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn write<W>(&self, wtr: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        use self::PropertyValue::*;
+        match self {
+            Bool(value) => wtr.write_u8(*value as u8)?,
+            Int8(value) => wtr.write_i8(*value)?,
+            UInt8(value) => wtr.write_u8(*value)?,
+            Int16(value) => wtr.write_i16::<LittleEndian>(*value)?,
+            UInt16(value) => wtr.write_u16::<LittleEndian>(*value)?,
+            Int32(value) => wtr.write_i32::<LittleEndian>(*value)?,
+            UInt32(value) => wtr.write_u32::<LittleEndian>(*value)?,
+            Int64(value) => wtr.write_i64::<LittleEndian>(*value)?,
+            UInt64(value) => wtr.write_u64::<LittleEndian>(*value)?,
+            Fixed(value) => wtr.write_i32::<LittleEndian>(value.0)?,
+            Float(value) => wtr.write_f32::<LittleEndian>(*value)?,
+            Double(value) => wtr.write_f64::<LittleEndian>(*value)?,
+            String(value) => write_string(wtr, value)?,
+            Point { x, y } => {
+                wtr.write_i32::<LittleEndian>(*x)?;
+                wtr.write_i32::<LittleEndian>(*y)?;
+            }
+            Size { width, height } => {
+                wtr.write_i32::<LittleEndian>(*width)?;
+                wtr.write_i32::<LittleEndian>(*height)?;
+            }
+            Rect {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                wtr.write_i32::<LittleEndian>(*x)?;
+                wtr.write_i32::<LittleEndian>(*y)?;
+                wtr.write_i32::<LittleEndian>(*width)?;
+                wtr.write_i32::<LittleEndian>(*height)?;
+            }
+            Vector {
+                element_type,
+                elements,
+            } => {
+                wtr.write_u32::<LittleEndian>(count_field(
+                    "Vector elements",
+                    elements.len(),
+                )?)?;
+                wtr.write_u16::<LittleEndian>(
+                    element_type.map_or(0, |t| t as u16),
+                )?;
+                for element in elements {
+                    match element_type {
+                        Some(t) => {
+                            if element.property_type() != *t {
+                                return Err(Error::invalid_data(format!(
+                                        "Vector element is {:?} but `element_type` is {:?}",
+                                        element.property_type(),
+                                        t
+                                    ),
+                                ));
+                            }
+                        }
+                        None => wtr.write_u16::<LittleEndian>(
+                            element.property_type() as u16,
+                        )?,
+                    }
+                    element.write(wtr)?;
+                }
+            }
+            Map(properties) => write_properties(wtr, properties)?,
+            Uuid(uuid) => wtr.write_all(uuid)?,
+        }
+        Ok(())
+    }
+}
+
+fn read_property_type<R>(read: &mut R) -> Result<PropertyType>
+where
+    R: Read,
+{
+    read.read_u16::<LittleEndian>()?
+        .try_into_PropertyType()
+        .map_err(Error::invalid_enum_value)
+}
+
+fn read_properties<R>(read: &mut R, depth: usize) -> Result<Vec<Property>>
+where
+    R: Read,
+{
+    let number_of_properties = read.read_u32::<LittleEndian>()?;
+    let mut properties = Vec::new();
+    for _ in 0..number_of_properties {
+        let name = read_string(read)?;
+        let property_type = read_property_type(read)?;
+        let value = PropertyValue::from_read(read, property_type, depth)?;
+        properties.push(Property { name, value });
+    }
+    Ok(properties)
+}
+
+fn write_properties<W>(wtr: &mut W, properties: &[Property]) -> Result<()>
+where
+    W: Write,
+{
+    wtr.write_u32::<LittleEndian>(count_field(
+        "Properties",
+        properties.len(),
+    )?)?;
+    for property in properties {
+        write_string(wtr, &property.name)?;
+        wtr.write_u16::<LittleEndian>(property.value.property_type() as u16)?;
+        property.value.write(wtr)?;
+    }
+    Ok(())
+}
+
+impl PropertiesMap {
+    /// find a property by name
+    pub fn get(&self, name: &str) -> Option<&PropertyValue> {
+        self.properties
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| &p.value)
+    }
+
+    /// resolve the External Files entry of the extension owning this map,
+    /// None for user properties
+    pub fn external_file<'a>(
+        &self,
+        ase: &'a Aseprite,
+    ) -> Option<&'a ExternalFile> {
+        if self.key == 0 {
+            return None;
+        }
+        ase.external_file(self.key)
+    }
+}
+
+impl Default for UserDataChunk {
+    fn default() -> Self {
+        UserDataChunk {
+            flags: Flags::empty(),
+            text: None,
+            color: None,
+            properties_maps: Vec::new(),
+        }
+    }
 }
 
 impl UserDataChunk {
-    pub fn from_read<R>(read: &mut R) -> io::Result<Self>
+    /// find the properties map stored under `key`
+    /// (0 for user properties, otherwise an External Files entry id)
+    pub fn properties(&self, key: u32) -> Option<&PropertiesMap> {
+        self.properties_maps.iter().find(|m| m.key == key)
+    }
+
+    pub fn from_read<R>(read: &mut R) -> Result<Self>
     where
         R: Read,
     {
@@ -41,11 +408,34 @@ impl UserDataChunk {
         } else {
             None
         };
+        let mut properties_maps = Vec::new();
+        if flags.contains(Flags::HasProperties) {
+            let size = read.read_u32::<LittleEndian>()? as usize;
+            if size < 8 {
+                return Err(Error::invalid_data(format!(
+                    "Invalid Properties Size {}",
+                    size
+                )));
+            }
+            let bytes = read_bytes(read, size - 4)?;
+            let mut read = &bytes[..];
+            let number_of_maps = read.read_u32::<LittleEndian>()?;
+            for _ in 0..number_of_maps {
+                let key = read.read_u32::<LittleEndian>()?;
+                let properties = read_properties(&mut read, 0)?;
+                properties_maps.push(PropertiesMap { key, properties });
+            }
+        }
 
-        Ok(Self { flags, text, color })
+        Ok(Self {
+            flags,
+            text,
+            color,
+            properties_maps,
+        })
     }
 
-    pub fn write<W>(&self, wtr: &mut W) -> io::Result<()>
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
     where
         W: Write,
     {
@@ -53,22 +443,20 @@ impl UserDataChunk {
         if self.flags.contains(Flags::HasText) {
             match &self.text {
                 None => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "Flag `HasText` is 1 but `text` is None".to_owned(),
+                    return Err(Error::invalid_data(
+                        "Flag `HasText` is 1 but `text` is None",
                     ));
                 }
                 Some(text) => {
-                    write_string(wtr, &text)?;
+                    write_string(wtr, text)?;
                 }
             }
         }
         if self.flags.contains(Flags::HasColor) {
             match &self.color {
                 None => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "Flag `HasColor` is 1 but `color` is None".to_owned(),
+                    return Err(Error::invalid_data(
+                        "Flag `HasColor` is 1 but `color` is None",
                     ));
                 }
                 Some(color) => {
@@ -79,6 +467,23 @@ impl UserDataChunk {
                 }
             }
         }
+        if self.flags.contains(Flags::HasProperties) {
+            let mut maps_buf = vec![];
+            maps_buf.write_u32::<LittleEndian>(count_field(
+                "Properties maps",
+                self.properties_maps.len(),
+            )?)?;
+            for map in &self.properties_maps {
+                maps_buf.write_u32::<LittleEndian>(map.key)?;
+                write_properties(&mut maps_buf, &map.properties)?;
+            }
+            wtr.write_u32::<LittleEndian>(block_size(
+                "Properties",
+                4,
+                maps_buf.len(),
+            )?)?;
+            wtr.write_all(&maps_buf)?;
+        }
         Ok(())
     }
 }
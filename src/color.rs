@@ -1,22 +1,25 @@
-use std::io::{self, Read, Write};
+use std::io::{Read, Write};
 
 use byteorder::{ReadBytesExt, WriteBytesExt};
 
-#[derive(Debug, Default, Clone, Copy)]
+use crate::error::{Error, Result};
+use crate::Palette;
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct RGB256 {
     pub r: u8,
     pub g: u8,
     pub b: u8,
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct RGB64 {
     pub r: u8,
     pub g: u8,
     pub b: u8,
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct RGBA256 {
     pub r: u8,
     pub g: u8,
@@ -24,7 +27,7 @@ pub struct RGBA256 {
     pub a: u8,
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct Grayscale256 {
     pub v: u8,
     pub a: u8,
@@ -38,22 +41,47 @@ pub enum Pixels {
 }
 
 impl Pixels {
-    pub fn rgba_from_read<R>(
-        read: &mut R,
-        pixels_size: usize,
-    ) -> io::Result<Self>
+    /// convert to RGBA, looking indices up in `palette`, indices equal to
+    /// `transparent_index` or missing from the palette become transparent
+    pub fn to_rgba(
+        &self,
+        palette: &Palette,
+        transparent_index: Option<u8>,
+    ) -> Vec<RGBA256> {
+        match self {
+            Pixels::RGBA(cols) => cols.clone(),
+            Pixels::Grayscale(cols) => cols
+                .iter()
+                .map(|c| RGBA256 {
+                    r: c.v,
+                    g: c.v,
+                    b: c.v,
+                    a: c.a,
+                })
+                .collect(),
+            Pixels::Indexed(indices) => indices
+                .iter()
+                .map(|&i| {
+                    if Some(i) == transparent_index {
+                        RGBA256::default()
+                    } else {
+                        palette.color(i as usize).unwrap_or_default()
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    pub fn rgba_from_read<R>(read: &mut R, pixels_size: usize) -> Result<Self>
     where
         R: Read,
     {
         const BYTES_PER_PIXEL: usize = 4;
-        if pixels_size % BYTES_PER_PIXEL != 0 {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!(
-                    "Pixels size is not multiple of 4 (RGBA): {}",
-                    pixels_size
-                ),
-            ));
+        if !pixels_size.is_multiple_of(BYTES_PER_PIXEL) {
+            return Err(Error::invalid_data(format!(
+                "Pixels size is not multiple of 4 (RGBA): {}",
+                pixels_size
+            )));
         }
 
         let pixel_count = pixels_size / BYTES_PER_PIXEL;
@@ -73,19 +101,16 @@ impl Pixels {
     pub fn grayscale_from_read<R>(
         read: &mut R,
         pixels_size: usize,
-    ) -> io::Result<Self>
+    ) -> Result<Self>
     where
         R: Read,
     {
         const BYTES_PER_PIXEL: usize = 2;
-        if pixels_size % BYTES_PER_PIXEL != 0 {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!(
-                    "Pixels size is not multiple of 2 (Grayscale): {}",
-                    pixels_size
-                ),
-            ));
+        if !pixels_size.is_multiple_of(BYTES_PER_PIXEL) {
+            return Err(Error::invalid_data(format!(
+                "Pixels size is not multiple of 2 (Grayscale): {}",
+                pixels_size
+            )));
         }
 
         let pixel_count = pixels_size / BYTES_PER_PIXEL;
@@ -104,7 +129,7 @@ impl Pixels {
     pub fn indexed_from_read<R>(
         read: &mut R,
         pixels_size: usize,
-    ) -> io::Result<Self>
+    ) -> Result<Self>
     where
         R: Read,
     {
@@ -118,7 +143,7 @@ impl Pixels {
         Ok(Pixels::Indexed(indices))
     }
 
-    pub fn write<W>(&self, wtr: &mut W) -> io::Result<()>
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
     where
         W: Write,
     {
@@ -139,7 +164,7 @@ impl Pixels {
                 }
             }
             Indexed(indices) => {
-                wtr.write(indices)?;
+                wtr.write_all(indices)?;
             }
         }
         Ok(())
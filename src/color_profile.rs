@@ -0,0 +1,285 @@
+use crate::color::RGBA256;
+use crate::error::{Error, Result};
+use crate::{ColorDepth, Image};
+
+/// sRGB primaries adapted to D50, the white point of ICC profiles, one
+/// column per primary
+const SRGB_TO_XYZ_D50: [[f64; 3]; 3] = [
+    [0.436_074_7, 0.385_064_9, 0.143_080_4],
+    [0.222_504_5, 0.716_878_6, 0.060_616_9],
+    [0.013_932_2, 0.097_104_5, 0.714_173_3],
+];
+
+const IDENTITY: [[f64; 3]; 3] =
+    [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// conversion of the colors of a color profile to sRGB
+#[derive(Debug, Clone)]
+pub struct SrgbTransform {
+    /// linear value of each of the 256 values of the red, green and blue
+    /// channels, empty for the identity
+    to_linear: Vec<[f64; 3]>,
+    /// from the linear RGB of the profile to linear sRGB
+    matrix: [[f64; 3]; 3],
+}
+
+impl SrgbTransform {
+    /// transform of colors that already are sRGB
+    pub fn identity() -> Self {
+        SrgbTransform {
+            to_linear: Vec::new(),
+            matrix: IDENTITY,
+        }
+    }
+
+    /// transform of sRGB primaries with a `value ^ gamma` transfer function
+    pub fn from_gamma(gamma: f64) -> Self {
+        let curve = (0..256).map(|i| (i as f64 / 255.0).powf(gamma));
+        SrgbTransform {
+            to_linear: curve.map(|v| [v, v, v]).collect(),
+            matrix: IDENTITY,
+        }
+    }
+
+    /// transform of an ICC profile, only RGB profiles made of a matrix and
+    /// of one curve per channel are supported
+    pub fn from_icc(icc: &[u8]) -> Result<Self> {
+        SrgbTransform::parse_icc(icc).ok_or_else(|| {
+            Error::invalid_data(
+                "Only RGB matrix/TRC ICC profiles are supported",
+            )
+        })
+    }
+
+    fn parse_icc(icc: &[u8]) -> Option<Self> {
+        if icc.get(16..20)? != b"RGB " || icc.get(20..24)? != b"XYZ " {
+            return None;
+        }
+        let curves = [
+            read_curve(icc_tag(icc, b"rTRC")?)?,
+            read_curve(icc_tag(icc, b"gTRC")?)?,
+            read_curve(icc_tag(icc, b"bTRC")?)?,
+        ];
+        let primaries = [
+            read_xyz(icc_tag(icc, b"rXYZ")?)?,
+            read_xyz(icc_tag(icc, b"gXYZ")?)?,
+            read_xyz(icc_tag(icc, b"bXYZ")?)?,
+        ];
+        let mut to_xyz = [[0.0; 3]; 3];
+        for (column, xyz) in primaries.iter().enumerate() {
+            for (row, value) in xyz.iter().enumerate() {
+                to_xyz[row][column] = *value;
+            }
+        }
+
+        Some(SrgbTransform {
+            to_linear: (0..256)
+                .map(|i| [curves[0][i], curves[1][i], curves[2][i]])
+                .collect(),
+            matrix: mul(&invert(&SRGB_TO_XYZ_D50)?, &to_xyz),
+        })
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.to_linear.is_empty() && self.matrix == IDENTITY
+    }
+
+    /// convert a color, alpha is kept as is
+    pub fn convert(&self, color: RGBA256) -> RGBA256 {
+        if self.is_identity() {
+            return color;
+        }
+        let linear = [
+            self.linear(color.r, 0),
+            self.linear(color.g, 1),
+            self.linear(color.b, 2),
+        ];
+        let channel = |row: &[f64; 3]| {
+            encode_srgb(row.iter().zip(&linear).map(|(m, v)| m * v).sum())
+        };
+        RGBA256 {
+            r: channel(&self.matrix[0]),
+            g: channel(&self.matrix[1]),
+            b: channel(&self.matrix[2]),
+            a: color.a,
+        }
+    }
+
+    /// convert every pixel of an image, does nothing when it isn't RGBA
+    pub fn convert_image(&self, image: &mut Image) {
+        if image.color_depth != ColorDepth::RGBA || self.is_identity() {
+            return;
+        }
+        for p in image.data.chunks_exact_mut(4) {
+            let c = self.convert(RGBA256 {
+                r: p[0],
+                g: p[1],
+                b: p[2],
+                a: p[3],
+            });
+            p.copy_from_slice(&[c.r, c.g, c.b, c.a]);
+        }
+    }
+
+    fn linear(&self, value: u8, channel: usize) -> f64 {
+        match self.to_linear.get(value as usize) {
+            Some(values) => values[channel],
+            None => decode_srgb(value),
+        }
+    }
+}
+
+fn decode_srgb(value: u8) -> f64 {
+    let v = value as f64 / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn encode_srgb(value: f64) -> u8 {
+    let v = value.clamp(0.0, 1.0);
+    let v = if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    };
+    (v * 255.0).round() as u8
+}
+
+fn be_u16(data: &[u8], at: usize) -> Option<u16> {
+    let bytes = data.get(at..at + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn be_u32(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// s15Fixed16Number
+fn be_fixed(data: &[u8], at: usize) -> Option<f64> {
+    Some(be_u32(data, at)? as i32 as f64 / 65536.0)
+}
+
+/// data of the tag with `signature` in the tag table of an ICC profile
+fn icc_tag<'a>(icc: &'a [u8], signature: &[u8; 4]) -> Option<&'a [u8]> {
+    let count = be_u32(icc, 128)? as usize;
+    (0..count.min(icc.len() / 12)).find_map(|i| {
+        let entry = 132 + i * 12;
+        if icc.get(entry..entry + 4)? != signature {
+            return None;
+        }
+        let offset = be_u32(icc, entry + 4)? as usize;
+        let size = be_u32(icc, entry + 8)? as usize;
+        icc.get(offset..offset.checked_add(size)?)
+    })
+}
+
+fn read_xyz(data: &[u8]) -> Option<[f64; 3]> {
+    if data.get(0..4)? != b"XYZ " {
+        return None;
+    }
+    Some([be_fixed(data, 8)?, be_fixed(data, 12)?, be_fixed(data, 16)?])
+}
+
+/// linear values of the 256 channel values from a curve or parametric
+/// curve tag
+fn read_curve(data: &[u8]) -> Option<Vec<f64>> {
+    let curve: Box<dyn Fn(f64) -> f64> = match data.get(0..4)? {
+        b"curv" => {
+            let count = be_u32(data, 8)? as usize;
+            match count {
+                0 => Box::new(|x| x),
+                1 => {
+                    // u8Fixed8Number
+                    let gamma = be_u16(data, 12)? as f64 / 256.0;
+                    Box::new(move |x: f64| x.powf(gamma))
+                }
+                _ => {
+                    let table = (0..count)
+                        .map(|i| Some(be_u16(data, 12 + i * 2)? as f64))
+                        .collect::<Option<Vec<_>>>()?;
+                    Box::new(move |x: f64| {
+                        let position = x * (count - 1) as f64;
+                        let i = (position.floor() as usize).min(count - 2);
+                        let t = position - i as f64;
+                        (table[i] * (1.0 - t) + table[i + 1] * t) / 65535.0
+                    })
+                }
+            }
+        }
+        b"para" => {
+            let function = be_u16(data, 8)?;
+            let param_count = [1, 3, 4, 5, 7].get(function as usize)?;
+            let mut p = [1.0, 1.0, 0.0, 1.0, 0.0, 0.0, 0.0];
+            for (i, param) in p.iter_mut().enumerate().take(*param_count) {
+                *param = be_fixed(data, 12 + i * 4)?;
+            }
+            let [g, a, b, c, d, e, f] = p;
+            Box::new(move |x: f64| match function {
+                0 => x.powf(g),
+                1 if x >= -b / a => (a * x + b).powf(g),
+                1 => 0.0,
+                2 if x >= -b / a => (a * x + b).powf(g) + c,
+                2 => c,
+                3 if x >= d => (a * x + b).powf(g),
+                3 => c * x,
+                _ if x >= d => (a * x + b).powf(g) + e,
+                _ => c * x + f,
+            })
+        }
+        _ => return None,
+    };
+    Some(
+        (0..256)
+            .map(|i| {
+                let v = curve(i as f64 / 255.0);
+                if v.is_nan() {
+                    0.0
+                } else {
+                    v.clamp(0.0, 1.0)
+                }
+            })
+            .collect(),
+    )
+}
+
+fn mul(a: &[[f64; 3]; 3], b: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut m = [[0.0; 3]; 3];
+    for (row, m_row) in m.iter_mut().enumerate() {
+        for (column, value) in m_row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[row][k] * b[k][column]).sum();
+        }
+    }
+    m
+}
+
+fn invert(m: &[[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+    let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| {
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let det = m[0][0] * cofactor(1, 2, 1, 2) - m[0][1] * cofactor(1, 2, 0, 2)
+        + m[0][2] * cofactor(1, 2, 0, 1);
+    if det == 0.0 {
+        return None;
+    }
+    Some([
+        [
+            cofactor(1, 2, 1, 2) / det,
+            -cofactor(0, 2, 1, 2) / det,
+            cofactor(0, 1, 1, 2) / det,
+        ],
+        [
+            -cofactor(1, 2, 0, 2) / det,
+            cofactor(0, 2, 0, 2) / det,
+            -cofactor(0, 1, 0, 2) / det,
+        ],
+        [
+            cofactor(1, 2, 0, 1) / det,
+            -cofactor(0, 2, 0, 1) / det,
+            cofactor(0, 1, 0, 1) / det,
+        ],
+    ])
+}
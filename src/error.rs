@@ -0,0 +1,217 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::string::FromUtf8Error;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// where an error happened, fields are None when they aren't known
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct Location {
+    /// byte offset in the stream
+    pub offset: Option<u64>,
+    /// index of the frame
+    pub frame: Option<usize>,
+    /// index of the chunk in its frame
+    pub chunk: Option<usize>,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = vec![];
+        if let Some(frame) = self.frame {
+            parts.push(format!("frame {}", frame));
+        }
+        if let Some(chunk) = self.chunk {
+            parts.push(format!("chunk {}", chunk));
+        }
+        if let Some(offset) = self.offset {
+            parts.push(format!("offset 0x{:X}", offset));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// the underlying reader or writer failed
+    Io {
+        source: io::Error,
+        location: Location,
+    },
+    /// a magic number doesn't match, the data is not an Aseprite file
+    BadMagic {
+        expected: u16,
+        found: u16,
+        location: Location,
+    },
+    UnknownChunkType {
+        chunk_type: u16,
+        location: Location,
+    },
+    /// a value doesn't match any variant of an enum like `CelType`
+    InvalidEnumValue {
+        message: String,
+        location: Location,
+    },
+    /// the data ends before what the file describes
+    Truncated {
+        location: Location,
+    },
+    /// zlib compressed data can't be inflated
+    Decompression {
+        source: io::Error,
+        location: Location,
+    },
+    InvalidUtf8 {
+        source: FromUtf8Error,
+        location: Location,
+    },
+    /// values that are inconsistent with each other
+    InvalidData {
+        message: String,
+        location: Location,
+    },
+}
+
+impl Error {
+    pub(crate) fn invalid_data<S: Into<String>>(message: S) -> Self {
+        Error::InvalidData {
+            message: message.into(),
+            location: Location::default(),
+        }
+    }
+
+    pub(crate) fn invalid_enum_value(message: String) -> Self {
+        Error::InvalidEnumValue {
+            message,
+            location: Location::default(),
+        }
+    }
+
+    pub(crate) fn decompression(source: io::Error) -> Self {
+        Error::Decompression {
+            source,
+            location: Location::default(),
+        }
+    }
+
+    pub fn location(&self) -> &Location {
+        match self {
+            Error::Io { location, .. }
+            | Error::BadMagic { location, .. }
+            | Error::UnknownChunkType { location, .. }
+            | Error::InvalidEnumValue { location, .. }
+            | Error::Truncated { location }
+            | Error::Decompression { location, .. }
+            | Error::InvalidUtf8 { location, .. }
+            | Error::InvalidData { location, .. } => location,
+        }
+    }
+
+    fn location_mut(&mut self) -> &mut Location {
+        match self {
+            Error::Io { location, .. }
+            | Error::BadMagic { location, .. }
+            | Error::UnknownChunkType { location, .. }
+            | Error::InvalidEnumValue { location, .. }
+            | Error::Truncated { location }
+            | Error::Decompression { location, .. }
+            | Error::InvalidUtf8 { location, .. }
+            | Error::InvalidData { location, .. } => location,
+        }
+    }
+
+    /// set the byte offset, unless a more precise one is already known
+    pub(crate) fn at_offset(mut self, offset: Option<u64>) -> Self {
+        let location = self.location_mut();
+        location.offset = location.offset.or(offset);
+        self
+    }
+
+    pub(crate) fn in_frame(mut self, frame: usize) -> Self {
+        self.location_mut().frame.get_or_insert(frame);
+        self
+    }
+
+    pub(crate) fn in_chunk(mut self, chunk: usize) -> Self {
+        self.location_mut().chunk.get_or_insert(chunk);
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { source, .. } => write!(f, "I/O error: {}", source),
+            Error::BadMagic {
+                expected, found, ..
+            } => write!(
+                f,
+                "Bad magic number 0x{:04X}, expected 0x{:04X}",
+                found, expected
+            ),
+            Error::UnknownChunkType { chunk_type, .. } => {
+                write!(f, "Invalid Chunk Type 0x{:X}", chunk_type)
+            }
+            Error::InvalidEnumValue { message, .. } => write!(f, "{}", message),
+            Error::Truncated { .. } => write!(f, "Unexpected end of data"),
+            Error::Decompression { source, .. } => {
+                write!(f, "Zlib decompression failed: {}", source)
+            }
+            Error::InvalidUtf8 { source, .. } => {
+                write!(f, "Invalid UTF-8 string: {}", source)
+            }
+            Error::InvalidData { message, .. } => write!(f, "{}", message),
+        }?;
+
+        let location = self.location();
+        if *location != Location::default() {
+            write!(f, " ({})", location)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } | Error::Decompression { source, .. } => {
+                Some(source)
+            }
+            Error::InvalidUtf8 { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Self {
+        let location = Location::default();
+        match source.kind() {
+            io::ErrorKind::UnexpectedEof => Error::Truncated { location },
+            _ => Error::Io { source, location },
+        }
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(source: FromUtf8Error) -> Self {
+        Error::InvalidUtf8 {
+            source,
+            location: Location::default(),
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Io { source, .. } => source,
+            Error::Truncated { .. } => {
+                io::Error::new(io::ErrorKind::UnexpectedEof, error)
+            }
+            error => io::Error::new(io::ErrorKind::InvalidData, error),
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt;
+
+/// FIXED: a 32-bit fixed point (16.16) value
+#[derive(Default, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Fixed(pub i32);
+
+impl Fixed {
+    pub const ONE: Fixed = Fixed(0x0001_0000);
+
+    pub fn from_f64(value: f64) -> Self {
+        Fixed((value * 65536.0).round() as i32)
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / 65536.0
+    }
+
+    pub fn from_f32(value: f32) -> Self {
+        Fixed::from_f64(value as f64)
+    }
+
+    pub fn to_f32(self) -> f32 {
+        self.to_f64() as f32
+    }
+}
+
+impl fmt::Debug for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Fixed({})", self.to_f64())
+    }
+}
+
+impl From<f32> for Fixed {
+    fn from(value: f32) -> Self {
+        Fixed::from_f32(value)
+    }
+}
+
+impl From<Fixed> for f32 {
+    fn from(value: Fixed) -> Self {
+        value.to_f32()
+    }
+}
@@ -1,14 +1,24 @@
-use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::convert::TryFrom;
+use std::io::{Read, Seek, SeekFrom, Write};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
-use crate::{Chunk, Header};
+use crate::error::{Error, Location, Result};
+use crate::helpers::{block_size, read_magic, remaining_len, write_zeros};
+use crate::{Chunk, ChunkData, Header, ReadMode, ReadOptions, WriteOptions};
 
+/// User Data chunks that annotate another chunk are attached to it when the
+/// frame is read, and are not in `chunks`, so `chunks` can hold fewer chunks
+/// than the file. Errors give the index of the chunk in the file, and
+/// `write` writes `Chunk::chunk_count` chunks for each of `chunks`.
 #[derive(Debug, Default)]
 pub struct Frame {
     pub byte_count: u32,
     pub frame_duration_milliseconds: u16,
+    /// old number of chunks, as in the file
     pub number_of_chunks_old: u16,
+    /// number of chunks in `chunks` once the User Data is attached and the
+    /// chunks a lenient read skipped are left out
     pub number_of_chunks: u32,
     pub chunks: Vec<Chunk>,
 }
@@ -29,12 +39,82 @@ impl Frame {
 
     const MAGIC: u16 = 0xF1FA;
 
-    pub fn from_read<R>(read: &mut R, header: &Header) -> io::Result<Self>
+    pub fn from_read<R>(read: &mut R, header: &Header) -> Result<Self>
     where
         R: Read + Seek,
     {
+        let options = ReadOptions::default();
+        Frame::from_read_with_options(read, header, &options, &mut vec![])
+    }
+
+    /// errors are located at the start of the frame, or of the chunk that
+    /// can't be read with its index, chunks skipped by a lenient read are
+    /// added to `warnings`
+    pub fn from_read_with_options<R>(
+        read: &mut R,
+        header: &Header,
+        options: &ReadOptions,
+        warnings: &mut Vec<Error>,
+    ) -> Result<Self>
+    where
+        R: Read + Seek,
+    {
+        let offset = read.stream_position().ok();
+        let mut frame_warnings = vec![];
+        let frame =
+            Self::read_frame(read, header, options, &mut frame_warnings)
+                .map_err(|e| e.at_offset(offset))?;
+        warnings
+            .extend(frame_warnings.into_iter().map(|e| e.at_offset(offset)));
+        Ok(frame)
+    }
+
+    /// move past a chunk that failed to load, using its size when it fits in
+    /// the frame, returns false when the rest of the frame was skipped
+    fn skip_chunk<R>(
+        read: &mut R,
+        chunk_start: u64,
+        frame_end: u64,
+    ) -> Result<bool>
+    where
+        R: Read + Seek,
+    {
+        if chunk_start + 4 <= frame_end {
+            read.seek(SeekFrom::Start(chunk_start))?;
+            let chunk_size = read.read_u32::<LittleEndian>()? as u64;
+            if chunk_size >= 6 && chunk_start + chunk_size <= frame_end {
+                read.seek(SeekFrom::Start(chunk_start + chunk_size))?;
+                return Ok(true);
+            }
+        }
+        read.seek(SeekFrom::Start(frame_end))?;
+        Ok(false)
+    }
+
+    fn read_frame<R>(
+        read: &mut R,
+        header: &Header,
+        options: &ReadOptions,
+        warnings: &mut Vec<Error>,
+    ) -> Result<Self>
+    where
+        R: Read + Seek,
+    {
+        let start = read.stream_position()?;
+        let remaining = remaining_len(read)?;
         let byte_count = read.read_u32::<LittleEndian>()?;
-        read.seek(SeekFrom::Current(2))?;
+        read_magic(read, Frame::MAGIC)?;
+        if byte_count < 16 {
+            return Err(Error::invalid_data(format!(
+                "Frame size {} is smaller than the frame header",
+                byte_count
+            )));
+        }
+        if byte_count as u64 > remaining {
+            return Err(Error::Truncated {
+                location: Location::default(),
+            });
+        }
         let number_of_chunks_old = read.read_u16::<LittleEndian>()?;
         let frame_duration_milliseconds = read.read_u16::<LittleEndian>()?;
         read.seek(SeekFrom::Current(2))?;
@@ -46,52 +126,183 @@ impl Frame {
             number_of_chunks_new
         };
 
-        let mut chunks = Vec::with_capacity(number_of_chunks as usize);
-        for _ in 0..number_of_chunks {
-            chunks.push(Chunk::from_read(read, header)?);
+        options.limits.check_number_of_chunks(number_of_chunks)?;
+
+        // a chunk is at least 6 bytes
+        let mut chunks: Vec<Chunk> = Vec::with_capacity(
+            (number_of_chunks as usize).min(byte_count as usize / 6),
+        );
+        let frame_end = start + byte_count as u64;
+        let lenient = options.mode == ReadMode::Lenient;
+        let mut user_data_owner: Option<usize> = None;
+        // whether user data was attached to the owner
+        let mut attached_user_data = false;
+        for i in 0..number_of_chunks as usize {
+            let chunk_start = read.stream_position()?;
+            let chunk =
+                match Chunk::from_read_with_options(read, header, options) {
+                    Ok(chunk) => chunk,
+                    Err(e) if lenient => {
+                        warnings.push(e.in_chunk(i));
+                        // user data after a skipped chunk is kept on its own
+                        user_data_owner = None;
+                        if Frame::skip_chunk(read, chunk_start, frame_end)? {
+                            continue;
+                        }
+                        break;
+                    }
+                    Err(e) => return Err(e.in_chunk(i)),
+                };
+            match chunk.chunk_data {
+                ChunkData::UserDataChunk(user_data) => {
+                    let user_data = match user_data_owner {
+                        Some(i) => {
+                            chunks[i].chunk_data.attach_user_data(user_data)
+                        }
+                        None => Some(user_data),
+                    };
+                    if let Some(user_data) = user_data {
+                        // later user data would be written before this one
+                        user_data_owner = None;
+                        chunks.push(Chunk {
+                            chunk_size: chunk.chunk_size,
+                            chunk_data: ChunkData::UserDataChunk(user_data),
+                        });
+                    } else {
+                        attached_user_data = true;
+                    }
+                }
+                ref chunk_data if chunk_data.is_skipped_by_user_data() => {
+                    // the owner's user data is written after the chunks that
+                    // skip it, so user data read before them is kept on its
+                    // own, and so is the user data that follows
+                    if let (Some(owner), true) =
+                        (user_data_owner, attached_user_data)
+                    {
+                        let user_data =
+                            chunks[owner].chunk_data.take_user_data();
+                        chunks.extend(
+                            user_data.into_iter().map(|u| {
+                                Chunk::new(ChunkData::UserDataChunk(u))
+                            }),
+                        );
+                        user_data_owner = None;
+                    }
+                    chunks.push(chunk);
+                }
+                _ => {
+                    user_data_owner = Some(chunks.len());
+                    attached_user_data = false;
+                    chunks.push(chunk);
+                }
+            }
+        }
+
+        let end = read.stream_position()?;
+        if end - start != byte_count as u64 {
+            let error = Error::invalid_data(format!(
+                "Frame size is {} bytes but its chunks end after {} bytes",
+                byte_count,
+                end - start
+            ));
+            if !lenient {
+                return Err(error);
+            }
+            warnings.push(error);
+            read.seek(SeekFrom::Start(frame_end))?;
         }
 
         Ok(Self {
             byte_count,
             number_of_chunks_old,
             frame_duration_milliseconds,
-            number_of_chunks,
+            number_of_chunks: chunks.len() as u32,
             chunks,
         })
     }
 
     const PREFER_OLD: bool = true;
 
-    pub fn write<W>(&self, wtr: &mut W) -> io::Result<()>
+    /// write the frame, cels are written as they are
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
     where
-        W: Write + Seek,
+        W: Write,
     {
-        let chunks_buf = vec![];
-        let mut chunks_wtr = Cursor::new(chunks_buf);
-        for chunk in &self.chunks {
-            chunk.write(&mut chunks_wtr)?;
-        }
+        self.write_frame(wtr, None)
+    }
 
-        wtr.write_u32::<LittleEndian>(16 + chunks_wtr.position() as u32)?;
-        wtr.write_u16::<LittleEndian>(Frame::MAGIC)?;
+    /// write the frame, `header` gives the color depth of its cels
+    pub fn write_with_options<W>(
+        &self,
+        wtr: &mut W,
+        header: &Header,
+        options: &WriteOptions,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        self.write_frame(wtr, Some((header, options)))
+    }
 
-        if Frame::PREFER_OLD {
-            wtr.write_u16::<LittleEndian>(self.chunks.len() as u16)?;
-        } else {
-            wtr.seek(SeekFrom::Current(2))?;
+    fn write_frame<W>(
+        &self,
+        wtr: &mut W,
+        options: Option<(&Header, &WriteOptions)>,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        let mut chunks_buf = vec![];
+        let mut i = 0;
+        while i < self.chunks.len() {
+            let chunk = &self.chunks[i];
+            chunk.write_data(&mut chunks_buf, options)?;
+            // the user data goes after the chunks that don't take it, like
+            // Aseprite writes it
+            i += 1;
+            while let Some(skipped) = self
+                .chunks
+                .get(i)
+                .filter(|c| c.chunk_data.is_skipped_by_user_data())
+            {
+                skipped.write_data(&mut chunks_buf, options)?;
+                skipped.write_user_data(&mut chunks_buf)?;
+                i += 1;
+            }
+            chunk.write_user_data(&mut chunks_buf)?;
         }
 
-        wtr.write_u16::<LittleEndian>(self.frame_duration_milliseconds)?;
-
-        wtr.seek(SeekFrom::Current(2))?;
+        let chunk_count: usize =
+            self.chunks.iter().map(Chunk::chunk_count).sum();
+        let chunk_count = u32::try_from(chunk_count).map_err(|_| {
+            Error::invalid_data(format!(
+                "Frame has {} chunks, more than {}",
+                chunk_count,
+                u32::MAX
+            ))
+        })?;
+        // an old count of 0xFFFF tells to read the new one
+        let (chunk_count_old, chunk_count_new) =
+            match u16::try_from(chunk_count) {
+                Ok(count) if Frame::PREFER_OLD && count != u16::MAX => {
+                    (count, 0)
+                }
+                _ if Frame::PREFER_OLD => (u16::MAX, chunk_count),
+                _ => (0, chunk_count),
+            };
 
-        if Frame::PREFER_OLD {
-            wtr.seek(SeekFrom::Current(4))?;
-        } else {
-            wtr.write_u32::<LittleEndian>(self.chunks.len() as u32)?;
-        }
+        wtr.write_u32::<LittleEndian>(block_size(
+            "Frame",
+            16,
+            chunks_buf.len(),
+        )?)?;
+        wtr.write_u16::<LittleEndian>(Frame::MAGIC)?;
+        wtr.write_u16::<LittleEndian>(chunk_count_old)?;
+        wtr.write_u16::<LittleEndian>(self.frame_duration_milliseconds)?;
+        write_zeros(wtr, 2)?;
+        wtr.write_u32::<LittleEndian>(chunk_count_new)?;
 
-        wtr.write(&chunks_wtr.into_inner())?;
+        wtr.write_all(&chunks_buf)?;
         Ok(())
     }
 }
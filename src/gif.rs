@@ -0,0 +1,408 @@
+/*
+GIF89a encoder for rendered frames, see
+https://www.w3.org/Graphics/GIF/spec-gif89a.txt
+
+Every frame is a full canvas image with its own local color table, its
+delay and a transparent color. Indexed sprites use the palette of each
+frame, RGBA sprites are quantized to 255 colors with median cut.
+*/
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::color::{RGB256, RGBA256};
+use crate::error::{Error, Result};
+use crate::{ColorDepth, Image, Sprite};
+
+/// pixels with a lower alpha are written as transparent
+const ALPHA_THRESHOLD: u8 = 128;
+
+/// the table starts over when this code would be added
+const MAX_CODE: u16 = 4095;
+
+/// frame of a GIF, its pixels are indices in `colors`
+struct GifFrame {
+    colors: Vec<RGB256>,
+    transparent_index: u8,
+    indices: Vec<u8>,
+    delay_centiseconds: u16,
+}
+
+fn rgb(c: RGBA256) -> RGB256 {
+    RGB256 {
+        r: c.r,
+        g: c.g,
+        b: c.b,
+    }
+}
+
+fn distance(a: RGB256, b: RGB256) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    d(a.r, b.r) + d(a.g, b.g) + d(a.b, b.b)
+}
+
+/// reduce `colors` with their pixel counts to at most `max_colors` by
+/// splitting the box with the widest channel range at its median
+fn median_cut(colors: Vec<(RGB256, u32)>, max_colors: usize) -> Vec<RGB256> {
+    let channel = |c: &RGB256, i: usize| [c.r, c.g, c.b][i];
+    let widest = |cube: &[(RGB256, u32)]| {
+        (0..3)
+            .map(|i| {
+                let values = cube.iter().map(|(c, _)| channel(c, i));
+                let min = values.clone().min().unwrap_or(0);
+                let max = values.max().unwrap_or(0);
+                (max - min, i)
+            })
+            .max()
+            .unwrap_or((0, 0))
+    };
+
+    let mut cubes = vec![colors];
+    while cubes.len() < max_colors {
+        let (index, (range, axis)) = match cubes
+            .iter()
+            .enumerate()
+            .filter(|(_, cube)| cube.len() > 1)
+            .map(|(i, cube)| (i, widest(cube)))
+            .max_by_key(|&(_, (range, _))| range)
+        {
+            Some(cube) => cube,
+            None => break,
+        };
+        if range == 0 {
+            break;
+        }
+
+        let mut cube = cubes.swap_remove(index);
+        cube.sort_by_key(|(c, _)| channel(c, axis));
+        let total: u64 = cube.iter().map(|&(_, n)| n as u64).sum();
+        let mut count = 0;
+        let mut split = 1;
+        for (i, &(_, n)) in cube.iter().enumerate() {
+            count += n as u64;
+            if count * 2 >= total {
+                split = (i + 1).min(cube.len() - 1);
+                break;
+            }
+        }
+        let other = cube.split_off(split);
+        cubes.push(cube);
+        cubes.push(other);
+    }
+
+    cubes
+        .iter()
+        .map(|cube| {
+            let total: u64 = cube.iter().map(|&(_, n)| n as u64).sum();
+            let average = |i: usize| {
+                let sum: u64 = cube
+                    .iter()
+                    .map(|(c, n)| channel(c, i) as u64 * *n as u64)
+                    .sum();
+                ((sum + total / 2) / total.max(1)) as u8
+            };
+            RGB256 {
+                r: average(0),
+                g: average(1),
+                b: average(2),
+            }
+        })
+        .collect()
+}
+
+/// map the opaque pixels of `image` to `colors`, picking the nearest color
+/// when there's no exact match
+fn map_colors(
+    image: &Image,
+    colors: &[RGB256],
+    transparent_index: u8,
+) -> Vec<u8> {
+    let mut lookup: HashMap<RGB256, u8> = HashMap::new();
+    for (i, &c) in colors.iter().enumerate() {
+        if i != transparent_index as usize {
+            lookup.entry(c).or_insert(i as u8);
+        }
+    }
+
+    image
+        .data
+        .chunks_exact(4)
+        .map(|p| {
+            if p[3] < ALPHA_THRESHOLD {
+                return transparent_index;
+            }
+            let c = RGB256 {
+                r: p[0],
+                g: p[1],
+                b: p[2],
+            };
+            *lookup.entry(c).or_insert_with(|| {
+                colors
+                    .iter()
+                    .enumerate()
+                    .filter(|&(i, _)| i != transparent_index as usize)
+                    .min_by_key(|&(_, &color)| distance(color, c))
+                    .map_or(transparent_index, |(i, _)| i as u8)
+            })
+        })
+        .collect()
+}
+
+/// build a GIF frame from a rendered RGBA image
+fn quantize(image: &Image) -> (Vec<RGB256>, u8, Vec<u8>) {
+    let mut counts: HashMap<RGB256, u32> = HashMap::new();
+    for p in image.data.chunks_exact(4) {
+        if p[3] >= ALPHA_THRESHOLD {
+            let c = RGB256 {
+                r: p[0],
+                g: p[1],
+                b: p[2],
+            };
+            *counts.entry(c).or_insert(0) += 1;
+        }
+    }
+    let mut colors: Vec<_> = counts.into_iter().collect();
+    // sort so the palette doesn't depend on the hash map order
+    colors.sort_by_key(|(c, _)| (c.r, c.g, c.b));
+
+    let mut colors = if colors.len() <= 255 {
+        colors.into_iter().map(|(c, _)| c).collect()
+    } else {
+        median_cut(colors, 255)
+    };
+    let transparent_index = colors.len() as u8;
+    colors.push(RGB256::default());
+
+    let indices = map_colors(image, &colors, transparent_index);
+    (colors, transparent_index, indices)
+}
+
+/// packs codes of variable width, least significant bit first
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u8) {
+        self.bits |= (code as u32) << self.bit_count;
+        self.bit_count += width as u32;
+        while self.bit_count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+        self.bytes
+    }
+}
+
+/// LZW compress `indices` with the variable code widths used by GIF
+fn lzw_encode(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let eoi = clear + 1;
+    let mut wtr = BitWriter::default();
+    let mut width = min_code_size + 1;
+    let mut hi = eoi;
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+
+    // the decoder adds a table entry for every code after the first one,
+    // the width grows once the last added code doesn't fit anymore and the
+    // table starts over once it's full, returns the added code
+    let mut next_code =
+        |wtr: &mut BitWriter,
+         width: &mut u8,
+         table: &mut HashMap<(u16, u8), u16>| {
+            hi += 1;
+            if hi == 1 << *width && *width < 12 {
+                *width += 1;
+            }
+            if hi == MAX_CODE {
+                wtr.write(clear, *width);
+                *width = min_code_size + 1;
+                hi = eoi;
+                table.clear();
+                return None;
+            }
+            Some(hi)
+        };
+
+    wtr.write(clear, width);
+    let mut iter = indices.iter();
+    if let Some(&first) = iter.next() {
+        let mut code = first as u16;
+        for &index in iter {
+            if let Some(&next) = table.get(&(code, index)) {
+                code = next;
+                continue;
+            }
+            wtr.write(code, width);
+            if let Some(added) = next_code(&mut wtr, &mut width, &mut table) {
+                table.insert((code, index), added);
+            }
+            code = index as u16;
+        }
+        wtr.write(code, width);
+        next_code(&mut wtr, &mut width, &mut table);
+    }
+    wtr.write(eoi, width);
+    wtr.finish()
+}
+
+fn write_sub_blocks<W>(wtr: &mut W, data: &[u8]) -> Result<()>
+where
+    W: Write,
+{
+    for block in data.chunks(255) {
+        wtr.write_u8(block.len() as u8)?;
+        wtr.write_all(block)?;
+    }
+    wtr.write_u8(0)?;
+    Ok(())
+}
+
+fn write_gif<W>(
+    wtr: &mut W,
+    width: u16,
+    height: u16,
+    frames: &[GifFrame],
+) -> Result<()>
+where
+    W: Write,
+{
+    wtr.write_all(b"GIF89a")?;
+    wtr.write_u16::<LittleEndian>(width)?;
+    wtr.write_u16::<LittleEndian>(height)?;
+    wtr.write_u8(0)?; // no global color table
+    wtr.write_u8(0)?; // background color index
+    wtr.write_u8(0)?; // pixel aspect ratio
+
+    // NETSCAPE2.0 application extension, loop forever
+    wtr.write_all(&[0x21, 0xFF, 0x0B])?;
+    wtr.write_all(b"NETSCAPE2.0")?;
+    wtr.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])?;
+
+    for frame in frames {
+        // graphic control extension, restore to background after each
+        // frame so transparent pixels don't show the previous one
+        wtr.write_all(&[0x21, 0xF9, 0x04])?;
+        wtr.write_u8((2 << 2) | 1)?;
+        wtr.write_u16::<LittleEndian>(frame.delay_centiseconds)?;
+        wtr.write_u8(frame.transparent_index)?;
+        wtr.write_u8(0)?;
+
+        let mut table_bits = 1;
+        while (1 << table_bits) < frame.colors.len() {
+            table_bits += 1;
+        }
+        wtr.write_u8(0x2C)?;
+        wtr.write_u16::<LittleEndian>(0)?;
+        wtr.write_u16::<LittleEndian>(0)?;
+        wtr.write_u16::<LittleEndian>(width)?;
+        wtr.write_u16::<LittleEndian>(height)?;
+        wtr.write_u8(0x80 | (table_bits - 1))?;
+        for i in 0..1 << table_bits {
+            let c = frame.colors.get(i).copied().unwrap_or_default();
+            wtr.write_all(&[c.r, c.g, c.b])?;
+        }
+
+        let min_code_size = table_bits.max(2);
+        wtr.write_u8(min_code_size)?;
+        write_sub_blocks(wtr, &lzw_encode(&frame.indices, min_code_size))?;
+    }
+
+    wtr.write_u8(0x3B)?;
+    Ok(())
+}
+
+impl Sprite {
+    fn gif_frame(&self, frame_index: usize) -> Result<GifFrame> {
+        let image = self.render_frame(frame_index)?;
+        let palette = self.palette_at(frame_index);
+
+        let (colors, transparent_index, indices) = if self.header().color_depth
+            == ColorDepth::Indexed
+            && !palette.is_empty()
+        {
+            let mut colors: Vec<_> = palette
+                .colors
+                .iter()
+                .take(256)
+                .map(|c| rgb(c.color))
+                .collect();
+            let mut transparent_index = self.header().transparent_palette_entry;
+            if transparent_index as usize >= colors.len() {
+                if colors.len() == 256 {
+                    transparent_index = 255;
+                } else {
+                    transparent_index = colors.len() as u8;
+                    colors.push(RGB256::default());
+                }
+            }
+            let indices = map_colors(&image, &colors, transparent_index);
+            (colors, transparent_index, indices)
+        } else {
+            quantize(&image)
+        };
+
+        let duration = self
+            .aseprite()
+            .frames
+            .get(frame_index)
+            .map_or(0, |frame| frame.frame_duration_milliseconds);
+
+        Ok(GifFrame {
+            colors,
+            transparent_index,
+            indices,
+            delay_centiseconds: duration.saturating_add(5) / 10,
+        })
+    }
+
+    fn write_gif_frames<W>(&self, wtr: &mut W, frames: &[usize]) -> Result<()>
+    where
+        W: Write,
+    {
+        let frames = frames
+            .iter()
+            .map(|&i| self.gif_frame(i))
+            .collect::<Result<Vec<_>>>()?;
+        write_gif(wtr, self.width(), self.height(), &frames)
+    }
+
+    /// write every frame as a looping GIF animation
+    pub fn write_gif<W>(&self, wtr: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        let frames: Vec<_> = (0..self.frame_count()).collect();
+        self.write_gif_frames(wtr, &frames)
+    }
+
+    /// write the frames of the tag named `tag_name` as a looping GIF
+    /// animation, played in the tag direction
+    pub fn write_tag_gif<W>(&self, wtr: &mut W, tag_name: &str) -> Result<()>
+    where
+        W: Write,
+    {
+        let tag = self.tag(tag_name).ok_or_else(|| {
+            Error::invalid_data(format!("Tag `{}` not found", tag_name))
+        })?;
+        let frames: Vec<_> = self
+            .tag_timeline(tag)
+            .frames
+            .iter()
+            .map(|&(frame, _)| frame)
+            .collect();
+        self.write_gif_frames(wtr, &frames)
+    }
+}
@@ -1,26 +1,35 @@
-use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::io::{Read, Seek, Write};
 
 use bitflags::bitflags;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use num_enum::CustomTryInto;
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, CustomTryInto)]
+use crate::error::{Error, Location, Result};
+use crate::helpers::{read_magic, remaining_len};
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, CustomTryInto)]
 #[repr(u16)]
 pub enum ColorDepth {
     Indexed = 8,
     Grayscale = 16,
+    #[default]
     RGBA = 32,
 }
 
-impl Default for ColorDepth {
-    fn default() -> Self {
-        ColorDepth::RGBA
-    }
-}
-
 bitflags! {
     pub struct Flags: u32 {
         const HasOpacity = 1;
+        const GroupHasOpacity = 2;
+    }
+}
+
+impl ColorDepth {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            ColorDepth::Indexed => 1,
+            ColorDepth::Grayscale => 2,
+            ColorDepth::RGBA => 4,
+        }
     }
 }
 
@@ -43,6 +52,16 @@ pub struct Header {
     pub number_of_colors: u16,
     pub pixel_width: u8,
     pub pixel_height: u8,
+    /// position of the grid, in pixels
+    pub grid_x: i16,
+    pub grid_y: i16,
+    /// size of a grid cell in pixels, zero when there is no grid
+    pub grid_width: u16,
+    pub grid_height: u16,
+    /// reserved bytes, kept so that files are written back unchanged
+    pub reserved_after_speed: [u8; 8],
+    pub reserved_after_transparent_palette_entry: [u8; 3],
+    pub reserved: [u8; 84],
 }
 
 impl Default for Header {
@@ -59,6 +78,13 @@ impl Default for Header {
             number_of_colors: 32,
             pixel_width: 1,
             pixel_height: 1,
+            grid_x: 0,
+            grid_y: 0,
+            grid_width: 16,
+            grid_height: 16,
+            reserved_after_speed: [0; 8],
+            reserved_after_transparent_palette_entry: [0; 3],
+            reserved: [0; 84],
         }
     }
 }
@@ -74,28 +100,62 @@ impl Header {
         }
     }
 
-    pub fn from_read<R>(read: &mut R) -> io::Result<Self>
+    pub fn from_read<R>(read: &mut R) -> Result<Self>
     where
         R: Read + Seek,
     {
+        let offset = read.stream_position().ok();
+        Self::read_header(read).map_err(|e| e.at_offset(offset))
+    }
+
+    fn read_header<R>(read: &mut R) -> Result<Self>
+    where
+        R: Read + Seek,
+    {
+        let remaining = remaining_len(read)?;
         let file_size = read.read_u32::<LittleEndian>()?;
-        read.seek(SeekFrom::Current(2))?;
+        read_magic(read, Header::MAGIC)?;
+        if file_size < 128 {
+            return Err(Error::invalid_data(format!(
+                "File size {} is smaller than the header",
+                file_size
+            )));
+        }
+        // the stream ends before the file, or has more than one file
+        if file_size as u64 > remaining {
+            return Err(Error::Truncated {
+                location: Location::default(),
+            });
+        }
+        if (file_size as u64) < remaining {
+            return Err(Error::invalid_data(format!(
+                "File size is {} bytes but the stream has {} bytes",
+                file_size, remaining
+            )));
+        }
         let frames = read.read_u16::<LittleEndian>()?;
         let width_in_pixels = read.read_u16::<LittleEndian>()?;
         let height_in_pixels = read.read_u16::<LittleEndian>()?;
         let color_depth = read
             .read_u16::<LittleEndian>()?
             .try_into_ColorDepth()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            .map_err(Error::invalid_enum_value)?;
         let flags = Flags::from_bits_truncate(read.read_u32::<LittleEndian>()?);
         let speed = read.read_u16::<LittleEndian>()?;
-        read.seek(SeekFrom::Current(4 + 4))?;
+        let mut reserved_after_speed = [0; 8];
+        read.read_exact(&mut reserved_after_speed)?;
         let transparent_palette_entry = read.read_u8()?;
-        read.seek(SeekFrom::Current(3))?;
+        let mut reserved_after_transparent_palette_entry = [0; 3];
+        read.read_exact(&mut reserved_after_transparent_palette_entry)?;
         let number_of_colors = read.read_u16::<LittleEndian>()?;
         let pixel_width = read.read_u8()?;
         let pixel_height = read.read_u8()?;
-        read.seek(SeekFrom::Current(92))?;
+        let grid_x = read.read_i16::<LittleEndian>()?;
+        let grid_y = read.read_i16::<LittleEndian>()?;
+        let grid_width = read.read_u16::<LittleEndian>()?;
+        let grid_height = read.read_u16::<LittleEndian>()?;
+        let mut reserved = [0; 84];
+        read.read_exact(&mut reserved)?;
 
         Ok(Self {
             file_size,
@@ -109,6 +169,13 @@ impl Header {
             number_of_colors,
             pixel_width,
             pixel_height,
+            grid_x,
+            grid_y,
+            grid_width,
+            grid_height,
+            reserved_after_speed,
+            reserved_after_transparent_palette_entry,
+            reserved,
         })
     }
 
@@ -117,11 +184,17 @@ impl Header {
         wtr: &mut W,
         frame_bytes: u32,
         frame_len: u16,
-    ) -> io::Result<()>
+    ) -> Result<()>
     where
-        W: Write + Seek,
+        W: Write,
     {
-        wtr.write_u32::<LittleEndian>(128 + frame_bytes)?;
+        let file_size = frame_bytes.checked_add(128).ok_or_else(|| {
+            Error::invalid_data(format!(
+                "File of {} bytes is too large for its size field",
+                128 + frame_bytes as u64
+            ))
+        })?;
+        wtr.write_u32::<LittleEndian>(file_size)?;
         wtr.write_u16::<LittleEndian>(Header::MAGIC)?;
         wtr.write_u16::<LittleEndian>(frame_len)?;
         wtr.write_u16::<LittleEndian>(self.width_in_pixels)?;
@@ -129,13 +202,17 @@ impl Header {
         wtr.write_u16::<LittleEndian>(self.color_depth as u16)?;
         wtr.write_u32::<LittleEndian>(self.flags.bits)?;
         wtr.write_u16::<LittleEndian>(self.speed)?;
-        wtr.seek(SeekFrom::Current(4 + 4))?;
+        wtr.write_all(&self.reserved_after_speed)?;
         wtr.write_u8(self.transparent_palette_entry)?;
-        wtr.seek(SeekFrom::Current(3))?;
+        wtr.write_all(&self.reserved_after_transparent_palette_entry)?;
         wtr.write_u16::<LittleEndian>(self.number_of_colors)?;
         wtr.write_u8(self.pixel_width)?;
         wtr.write_u8(self.pixel_height)?;
-        wtr.seek(SeekFrom::Current(92))?;
+        wtr.write_i16::<LittleEndian>(self.grid_x)?;
+        wtr.write_i16::<LittleEndian>(self.grid_y)?;
+        wtr.write_u16::<LittleEndian>(self.grid_width)?;
+        wtr.write_u16::<LittleEndian>(self.grid_height)?;
+        wtr.write_all(&self.reserved)?;
 
         Ok(())
     }
@@ -1,32 +1,170 @@
-use std::io::{self, Read, Write};
+use std::convert::TryFrom;
+use std::io::{self, Read, Seek, SeekFrom, Write};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
 
-pub fn read_bytes<R>(read: &mut R, length: usize) -> io::Result<Vec<u8>>
+use crate::error::{Error, Location, Result};
+
+/// read `length` bytes, the buffer only grows with the bytes actually read
+/// so a bogus length can't allocate more than the stream holds
+pub fn read_bytes<R>(read: &mut R, length: usize) -> Result<Vec<u8>>
 where
     R: Read,
 {
-    let mut bytes = Vec::with_capacity(length);
-    bytes.resize(length, 0);
-    read.read_exact(&mut bytes[..])?;
+    let mut bytes = Vec::new();
+    read.take(length as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != length {
+        return Err(Error::Truncated {
+            location: Location::default(),
+        });
+    }
     Ok(bytes)
 }
 
-pub fn read_string<R>(read: &mut R) -> io::Result<String>
+/// read a WORD and check that it's `expected`
+pub fn read_magic<R>(read: &mut R, expected: u16) -> Result<()>
+where
+    R: Read + Seek,
+{
+    let offset = read.stream_position()?;
+    let found = read.read_u16::<LittleEndian>()?;
+    if found != expected {
+        return Err(Error::BadMagic {
+            expected,
+            found,
+            location: Location {
+                offset: Some(offset),
+                ..Location::default()
+            },
+        });
+    }
+    Ok(())
+}
+
+/// number of bytes after the current position, the position is kept
+pub fn remaining_len<R>(read: &mut R) -> Result<u64>
+where
+    R: Seek,
+{
+    let position = read.stream_position()?;
+    let end = read.seek(SeekFrom::End(0))?;
+    read.seek(SeekFrom::Start(position))?;
+    Ok(end.saturating_sub(position))
+}
+
+/// capacity for `count` elements of at least `element_size` bytes, bounded
+/// by the bytes left in the stream
+pub fn bounded_capacity<R>(
+    read: &mut R,
+    count: usize,
+    element_size: usize,
+) -> Result<usize>
+where
+    R: Seek,
+{
+    let remaining = remaining_len(read)? as usize;
+    Ok(count.min(remaining / element_size.max(1)))
+}
+
+/// deflate `data` with a zlib `level` from 0 (fastest) to 9 (smallest),
+/// higher levels are clamped to 9
+pub fn zlib_compress(data: &[u8], level: u32) -> Result<Vec<u8>> {
+    let mut encoder =
+        ZlibEncoder::new(Vec::new(), Compression::new(level.min(9)));
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+pub fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>> {
+    zlib_decompress_bounded(data, usize::MAX)
+}
+
+/// inflate zlib data, failing if it inflates to more than `max_size` bytes
+pub fn zlib_decompress_bounded(
+    data: &[u8],
+    max_size: usize,
+) -> Result<Vec<u8>> {
+    let limit = (max_size as u64).saturating_add(1);
+    let mut decoder = ZlibDecoder::new(data).take(limit);
+    let mut bytes = Vec::new();
+    decoder
+        .read_to_end(&mut bytes)
+        .map_err(Error::decompression)?;
+    if bytes.len() > max_size {
+        return Err(Error::decompression(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Data inflates to more than {} bytes", max_size),
+        )));
+    }
+    Ok(bytes)
+}
+
+pub fn read_string<R>(read: &mut R) -> Result<String>
 where
     R: Read,
 {
     let length = read.read_u16::<LittleEndian>()? as usize;
     let bytes = read_bytes(read, length)?;
-    String::from_utf8(bytes)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    Ok(String::from_utf8(bytes)?)
+}
+
+pub fn write_string<W>(wtr: &mut W, string: &str) -> Result<()>
+where
+    W: Write,
+{
+    let length = u16::try_from(string.len()).map_err(|_| {
+        Error::invalid_data(format!(
+            "String of {} bytes is longer than {} bytes",
+            string.len(),
+            u16::MAX
+        ))
+    })?;
+    wtr.write_u16::<LittleEndian>(length)?;
+    wtr.write_all(string.as_bytes())?;
+    Ok(())
+}
+
+/// size of a file, frame or chunk made of a `header_len` bytes header and
+/// `len` bytes of data, an error when it doesn't fit in its u32 size field
+pub fn block_size(what: &str, header_len: u32, len: usize) -> Result<u32> {
+    u32::try_from(len)
+        .ok()
+        .and_then(|len| len.checked_add(header_len))
+        .ok_or_else(|| {
+            Error::invalid_data(format!(
+                "{} of {} bytes is too large for its size field",
+                what,
+                header_len as u64 + len as u64
+            ))
+        })
 }
 
-pub fn write_string<W>(wtr: &mut W, string: &str) -> io::Result<()>
+/// `count` items as written in a u32 count field, an error when it doesn't
+/// fit
+pub fn count_field(what: &str, count: usize) -> Result<u32> {
+    u32::try_from(count).map_err(|_| {
+        Error::invalid_data(format!(
+            "{} {} are too many for their count field",
+            count, what
+        ))
+    })
+}
+
+/// write `count` zero bytes, used for the reserved fields
+pub fn write_zeros<W>(wtr: &mut W, count: usize) -> Result<()>
 where
     W: Write,
 {
-    wtr.write_u16::<LittleEndian>(string.len() as u16)?;
-    wtr.write(&string.as_bytes())?;
+    wtr.write_all(&vec![0; count])?;
     Ok(())
 }
+
+/// multiply two 0-255 values as if they were in the 0-1 range,
+/// rounding like Aseprite does
+pub fn mul_un8(a: u8, b: u8) -> u8 {
+    let t = a as u32 * b as u32 + 0x80;
+    (((t >> 8) + t) >> 8) as u8
+}
@@ -0,0 +1,225 @@
+use crate::color::{Grayscale256, Pixels, RGBA256};
+use crate::error::{Error, Result};
+use crate::{ColorDepth, Limits, Palette};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Pixel {
+    RGBA(RGBA256),
+    Grayscale(Grayscale256),
+    Indexed(u8),
+}
+
+/// decoded image with its pixels stored row by row in a contiguous buffer,
+/// using the byte layout of `color_depth`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub color_depth: ColorDepth,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    /// create a new image with all bytes set to zero, an image over the
+    /// default `Limits::max_image_size` is an error
+    pub fn new(
+        width: u32,
+        height: u32,
+        color_depth: ColorDepth,
+    ) -> Result<Self> {
+        Image::new_with_limits(width, height, color_depth, &Limits::default())
+    }
+
+    /// create a new image with all bytes set to zero, an image over
+    /// `limits.max_image_size` is an error
+    pub fn new_with_limits(
+        width: u32,
+        height: u32,
+        color_depth: ColorDepth,
+        limits: &Limits,
+    ) -> Result<Self> {
+        limits.check_image_size("Image", width, height, color_depth)?;
+        let len = Image::data_len(width, height, color_depth);
+        Ok(Image {
+            width,
+            height,
+            color_depth,
+            data: vec![0; len],
+        })
+    }
+
+    /// number of bytes of a `width` x `height` image, saturating instead of
+    /// overflowing
+    pub fn data_len(width: u32, height: u32, color_depth: ColorDepth) -> usize {
+        (width as usize)
+            .saturating_mul(height as usize)
+            .saturating_mul(color_depth.bytes_per_pixel())
+    }
+
+    pub fn from_data(
+        width: u32,
+        height: u32,
+        color_depth: ColorDepth,
+        data: Vec<u8>,
+    ) -> Result<Self> {
+        let len = Image::data_len(width, height, color_depth);
+        if data.len() != len {
+            return Err(Error::invalid_data(format!(
+                "Image data is {} bytes but {}x{} {:?} needs {}",
+                data.len(),
+                width,
+                height,
+                color_depth,
+                len
+            )));
+        }
+
+        Ok(Image {
+            width,
+            height,
+            color_depth,
+            data,
+        })
+    }
+
+    pub fn from_pixels(
+        width: u32,
+        height: u32,
+        pixels: &Pixels,
+    ) -> Result<Self> {
+        let color_depth = match pixels {
+            Pixels::RGBA(_) => ColorDepth::RGBA,
+            Pixels::Grayscale(_) => ColorDepth::Grayscale,
+            Pixels::Indexed(_) => ColorDepth::Indexed,
+        };
+        let mut data = Vec::new();
+        pixels.write(&mut data)?;
+        Image::from_data(width, height, color_depth, data)
+    }
+
+    pub fn to_pixels(&self) -> Pixels {
+        match self.color_depth {
+            ColorDepth::RGBA => Pixels::RGBA(
+                self.data
+                    .chunks_exact(4)
+                    .map(|p| RGBA256 {
+                        r: p[0],
+                        g: p[1],
+                        b: p[2],
+                        a: p[3],
+                    })
+                    .collect(),
+            ),
+            ColorDepth::Grayscale => Pixels::Grayscale(
+                self.data
+                    .chunks_exact(2)
+                    .map(|p| Grayscale256 { v: p[0], a: p[1] })
+                    .collect(),
+            ),
+            ColorDepth::Indexed => Pixels::Indexed(self.data.clone()),
+        }
+    }
+
+    /// convert to a RGBA image, see `Pixels::to_rgba`
+    pub fn to_rgba(
+        &self,
+        palette: &Palette,
+        transparent_index: Option<u8>,
+    ) -> Image {
+        if self.color_depth == ColorDepth::RGBA {
+            return self.clone();
+        }
+        let mut data = Vec::with_capacity(self.data.len() * 4);
+        for c in self.to_pixels().to_rgba(palette, transparent_index) {
+            data.extend_from_slice(&[c.r, c.g, c.b, c.a]);
+        }
+        Image {
+            width: self.width,
+            height: self.height,
+            color_depth: ColorDepth::RGBA,
+            data,
+        }
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.color_depth.bytes_per_pixel()
+    }
+
+    /// number of bytes of a row
+    pub fn stride(&self) -> usize {
+        self.width as usize * self.bytes_per_pixel()
+    }
+
+    fn offset(&self, x: u32, y: u32) -> Option<usize> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(y as usize * self.stride() + x as usize * self.bytes_per_pixel())
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Option<Pixel> {
+        let i = self.offset(x, y)?;
+        let p = self.data.get(i..i + self.bytes_per_pixel())?;
+        let pixel = match self.color_depth {
+            ColorDepth::RGBA => Pixel::RGBA(RGBA256 {
+                r: p[0],
+                g: p[1],
+                b: p[2],
+                a: p[3],
+            }),
+            ColorDepth::Grayscale => {
+                Pixel::Grayscale(Grayscale256 { v: p[0], a: p[1] })
+            }
+            ColorDepth::Indexed => Pixel::Indexed(p[0]),
+        };
+        Some(pixel)
+    }
+
+    /// set a pixel, does nothing when it's outside the image or
+    /// `pixel` doesn't match the image color depth
+    pub fn set(&mut self, x: u32, y: u32, pixel: Pixel) {
+        let i = match self.offset(x, y) {
+            Some(i) => i,
+            None => return,
+        };
+        let bytes_per_pixel = self.bytes_per_pixel();
+        let p = match self.data.get_mut(i..i + bytes_per_pixel) {
+            Some(p) => p,
+            None => return,
+        };
+        match (self.color_depth, pixel) {
+            (ColorDepth::RGBA, Pixel::RGBA(c)) => {
+                p.copy_from_slice(&[c.r, c.g, c.b, c.a])
+            }
+            (ColorDepth::Grayscale, Pixel::Grayscale(c)) => {
+                p.copy_from_slice(&[c.v, c.a])
+            }
+            (ColorDepth::Indexed, Pixel::Indexed(index)) => p[0] = index,
+            _ => (),
+        }
+    }
+
+    /// `None` when `y` is outside the image or `data` is too short for it
+    pub fn row(&self, y: u32) -> Option<&[u8]> {
+        if y >= self.height {
+            return None;
+        }
+        let stride = self.stride();
+        let start = y as usize * stride;
+        self.data.get(start..start + stride)
+    }
+
+    pub fn row_mut(&mut self, y: u32) -> Option<&mut [u8]> {
+        if y >= self.height {
+            return None;
+        }
+        let stride = self.stride();
+        let start = y as usize * stride;
+        self.data.get_mut(start..start + stride)
+    }
+
+    /// the rows of the image, stopping early when `data` is too short
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        (0..self.height).map_while(move |y| self.row(y))
+    }
+}
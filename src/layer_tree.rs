@@ -0,0 +1,126 @@
+use crate::helpers::mul_un8;
+use crate::{header, layer_chunk, Header, LayerChunk, LayerType};
+
+#[derive(Debug, Clone)]
+pub struct LayerNode {
+    pub index: usize,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub visible: bool,
+    pub opacity: u8,
+    pub path: String,
+}
+
+/// layer hierarchy rebuilt from `LayerChunk::layer_child_level`,
+/// layers and children are ordered from the bottom to the top of the stack
+#[derive(Debug, Clone, Default)]
+pub struct LayerTree {
+    nodes: Vec<LayerNode>,
+    roots: Vec<usize>,
+}
+
+impl LayerTree {
+    pub fn new(layers: &[&LayerChunk], header: &Header) -> Self {
+        let layer_opacity_valid =
+            header.flags.contains(header::Flags::HasOpacity);
+        let group_opacity_valid =
+            header.flags.contains(header::Flags::GroupHasOpacity);
+
+        let mut nodes: Vec<LayerNode> = Vec::with_capacity(layers.len());
+        let mut roots = Vec::new();
+        // last layer seen at each child level
+        let mut stack: Vec<usize> = Vec::new();
+
+        for (index, layer) in layers.iter().enumerate() {
+            let level = layer.layer_child_level as usize;
+            stack.truncate(level);
+            let parent = stack.last().copied();
+
+            let opacity = if !layer_opacity_valid
+                || (layer.layer_type == LayerType::Group
+                    && !group_opacity_valid)
+            {
+                255
+            } else {
+                layer.opacity
+            };
+            let visible = layer.flags.contains(layer_chunk::Flags::Visible);
+
+            let node = match parent {
+                Some(parent) => {
+                    let p = &mut nodes[parent];
+                    p.children.push(index);
+                    LayerNode {
+                        index,
+                        parent: Some(parent),
+                        children: Vec::new(),
+                        visible: visible && p.visible,
+                        opacity: mul_un8(opacity, p.opacity),
+                        path: format!("{}/{}", p.path, layer.layer_name),
+                    }
+                }
+                None => {
+                    roots.push(index);
+                    LayerNode {
+                        index,
+                        parent: None,
+                        children: Vec::new(),
+                        visible,
+                        opacity,
+                        path: layer.layer_name.clone(),
+                    }
+                }
+            };
+            nodes.push(node);
+
+            if layer.layer_type == LayerType::Group {
+                stack.push(index);
+            }
+        }
+
+        Self { nodes, roots }
+    }
+
+    /// layers that are not inside a group
+    pub fn roots(&self) -> &[usize] {
+        &self.roots
+    }
+
+    pub fn nodes(&self) -> &[LayerNode] {
+        &self.nodes
+    }
+
+    pub fn node(&self, layer_index: usize) -> Option<&LayerNode> {
+        self.nodes.get(layer_index)
+    }
+
+    pub fn parent(&self, layer_index: usize) -> Option<usize> {
+        self.nodes.get(layer_index)?.parent
+    }
+
+    pub fn children(&self, layer_index: usize) -> &[usize] {
+        self.nodes
+            .get(layer_index)
+            .map_or(&[], |node| &node.children[..])
+    }
+
+    /// visible only if the layer and all of its parent groups are visible
+    pub fn is_visible(&self, layer_index: usize) -> bool {
+        self.nodes.get(layer_index).is_some_and(|node| node.visible)
+    }
+
+    /// layer opacity multiplied by the opacity of its parent groups
+    pub fn opacity(&self, layer_index: usize) -> u8 {
+        self.nodes.get(layer_index).map_or(0, |node| node.opacity)
+    }
+
+    /// find a layer by the names of its groups and its own name joined
+    /// with '/', like "Characters/Hero/Body"
+    pub fn find(&self, path: &str) -> Option<usize> {
+        let path = path.trim_matches('/');
+        self.nodes
+            .iter()
+            .find(|node| node.path == path)
+            .map(|node| node.index)
+    }
+}
@@ -1,5 +1,9 @@
-#![allow(non_upper_case_globals)]
-use std::io::{self, Cursor, Read, Seek, Write};
+#![allow(non_upper_case_globals, ambiguous_glob_reexports)]
+use std::convert::TryFrom;
+use std::io::{Read, Seek, Write};
+
+use crate::error::Result;
+use crate::helpers::block_size;
 
 pub mod header;
 pub use self::header::*;
@@ -10,9 +14,38 @@ pub use self::frame::*;
 pub mod chunk;
 pub use self::chunk::*;
 
+pub mod layer_tree;
+pub use self::layer_tree::*;
+
+pub mod sprite;
+pub use self::sprite::*;
+
+pub mod blend;
 pub mod color;
+pub mod color_profile;
+pub mod error;
+pub mod fixed;
+pub mod gif;
 pub mod helpers;
+pub mod image;
+pub mod palette;
+pub mod png;
+pub mod read_options;
+mod render;
+pub mod sheet;
+pub mod timeline;
+pub mod write_options;
 pub use self::color::*;
+pub use self::color_profile::*;
+pub use self::error::{Error, Location};
+pub use self::fixed::*;
+pub use self::image::*;
+pub use self::palette::*;
+pub use self::png::*;
+pub use self::read_options::*;
+pub use self::sheet::*;
+pub use self::timeline::*;
+pub use self::write_options::*;
 
 /*
 https://github.com/aseprite/aseprite/blob/master/docs/ase-file-specs.md
@@ -39,38 +72,132 @@ PIXEL: One pixel, depending on the image pixel format:
 pub struct Aseprite {
     pub header: Header,
     pub frames: Vec<Frame>,
+    /// why chunks were skipped by a lenient read, located where they start
+    pub warnings: Vec<Error>,
 }
 
 impl Aseprite {
     pub fn new(header: Header, frames: Vec<Frame>) -> Self {
-        Self { header, frames }
+        Self {
+            header,
+            frames,
+            warnings: vec![],
+        }
+    }
+
+    pub fn from_read<R>(read: &mut R) -> Result<Aseprite>
+    where
+        R: Read + Seek,
+    {
+        Aseprite::from_read_with_options(read, &ReadOptions::default())
     }
 
-    pub fn from_read<R>(read: &mut R) -> io::Result<Aseprite>
+    /// read a file, rejecting it when it goes over `options.limits`, see
+    /// `ReadMode` for how chunks that can't be read are handled
+    pub fn from_read_with_options<R>(
+        read: &mut R,
+        options: &ReadOptions,
+    ) -> Result<Aseprite>
     where
         R: Read + Seek,
     {
+        let start = read.stream_position()?;
         let header = Header::from_read(read)?;
-        let mut frames = Vec::with_capacity(header.frames as usize);
-        for _ in 0..header.frames {
-            frames.push(Frame::from_read(read, &header)?);
+        options
+            .limits
+            .check_header(&header)
+            .map_err(|e| e.at_offset(Some(start)))?;
+        // a frame is at least 16 bytes
+        let mut frames = Vec::with_capacity(
+            (header.frames as usize).min(header.file_size as usize / 16),
+        );
+        let mut warnings = vec![];
+        for i in 0..header.frames as usize {
+            let mut frame_warnings = vec![];
+            let frame = Frame::from_read_with_options(
+                read,
+                &header,
+                options,
+                &mut frame_warnings,
+            )
+            .map_err(|e| e.in_frame(i))?;
+            frames.push(frame);
+            warnings.extend(frame_warnings.into_iter().map(|e| e.in_frame(i)));
         }
 
-        Ok(Self { header, frames })
+        let end = read.stream_position()?;
+        if end - start != header.file_size as u64 {
+            return Err(Error::invalid_data(format!(
+                "File size is {} bytes but its frames end after {} bytes",
+                header.file_size,
+                end - start
+            ))
+            .at_offset(Some(start)));
+        }
+
+        Ok(Self {
+            header,
+            frames,
+            warnings,
+        })
+    }
+
+    /// User Data chunk of the sprite, stored after the palette of the first
+    /// frame
+    pub fn user_data(&self) -> Option<&UserDataChunk> {
+        self.frames
+            .first()?
+            .chunks
+            .iter()
+            .filter_map(|chunk| match &chunk.chunk_data {
+                ChunkData::PaletteChunk(palette) => palette.user_data(),
+                _ => None,
+            })
+            .next()
+    }
+
+    /// find the entry referenced by `entry_id` in the External Files chunks
+    pub fn external_file(&self, entry_id: u32) -> Option<&ExternalFile> {
+        self.frames
+            .iter()
+            .flat_map(|frame| frame.chunks.iter())
+            .filter_map(|chunk| match &chunk.chunk_data {
+                ChunkData::ExternalFilesChunk(files) => files.entry(entry_id),
+                _ => None,
+            })
+            .next()
+    }
+
+    pub fn write<W>(&self, wtr: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        self.write_with_options(wtr, &WriteOptions::default())
     }
 
-    pub fn write<W>(&self, wtr: &mut W) -> io::Result<()>
+    /// write the file, changing how cels are stored as set in `options`
+    pub fn write_with_options<W>(
+        &self,
+        wtr: &mut W,
+        options: &WriteOptions,
+    ) -> Result<()>
     where
-        W: Write + Seek,
+        W: Write,
     {
-        let frames_buf = vec![];
-        let mut frames_wtr = Cursor::new(frames_buf);
+        let mut frames_buf = vec![];
         for frame in &self.frames {
-            frame.write(&mut frames_wtr)?;
+            frame.write_with_options(&mut frames_buf, &self.header, options)?;
         }
-        let body_len = frames_wtr.position() as u32;
-        self.header.write(wtr, body_len, self.frames.len() as u16)?;
-        wtr.write(&frames_wtr.into_inner())?;
+        let body_len = block_size("File body", 0, frames_buf.len())?;
+        let frame_len = u16::try_from(self.frames.len()).map_err(|_| {
+            Error::invalid_data(format!(
+                "Sprite has {} frames, more than {}",
+                self.frames.len(),
+                u16::MAX
+            ))
+        })?;
+        self.header.write(wtr, body_len, frame_len)?;
+        wtr.write_all(&frames_buf)?;
         Ok(())
     }
 }
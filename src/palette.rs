@@ -0,0 +1,157 @@
+use crate::color::{RGB256, RGBA256};
+use crate::{
+    ChunkData, Frame, OldPaletteChunk11, OldPaletteChunk4, PaletteChunk,
+};
+
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct PaletteColor {
+    pub color: RGBA256,
+    pub name: Option<String>,
+}
+
+/// colors referenced by the indices of `Pixels::Indexed`
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct Palette {
+    pub colors: Vec<PaletteColor>,
+}
+
+impl Palette {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    pub fn color(&self, index: usize) -> Option<RGBA256> {
+        self.colors.get(index).map(|c| c.color)
+    }
+
+    pub fn color_name(&self, index: usize) -> Option<&str> {
+        self.colors.get(index)?.name.as_deref()
+    }
+
+    fn set_color(&mut self, index: usize, color: PaletteColor) {
+        if index >= self.colors.len() {
+            self.colors.resize(index + 1, PaletteColor::default());
+        }
+        self.colors[index] = color;
+    }
+
+    /// palette set by the chunks of a frame, see `apply_frame`
+    pub fn from_frame(frame: &Frame) -> Self {
+        let mut palette = Palette::new();
+        palette.apply_frame(frame);
+        palette
+    }
+
+    /// palette in effect at `frame_index`, replaying the palette chunks of
+    /// every frame up to it
+    pub fn at_frame(frames: &[Frame], frame_index: usize) -> Self {
+        let mut palette = Palette::new();
+        for frame in frames.iter().take(frame_index + 1) {
+            palette.apply_frame(frame);
+        }
+        palette
+    }
+
+    /// apply the Palette chunks of a frame, or its old palette chunks when
+    /// there's none
+    pub fn apply_frame(&mut self, frame: &Frame) {
+        let chunks = || frame.chunks.iter().map(|c| &c.chunk_data);
+        let mut has_palette_chunk = false;
+        for chunk in chunks() {
+            if let ChunkData::PaletteChunk(chunk) = chunk {
+                self.apply_palette_chunk(chunk);
+                has_palette_chunk = true;
+            }
+        }
+        if has_palette_chunk {
+            return;
+        }
+
+        for chunk in chunks() {
+            match chunk {
+                ChunkData::OldPaletteChunk4(chunk) => {
+                    self.apply_old_palette_chunk4(chunk)
+                }
+                ChunkData::OldPaletteChunk11(chunk) => {
+                    self.apply_old_palette_chunk11(chunk)
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// apply the entries of a Palette chunk, which resizes the palette and
+    /// changes the colors in its [from, to] range, entries past the new
+    /// size are ignored
+    pub fn apply_palette_chunk(&mut self, chunk: &PaletteChunk) {
+        let size = chunk.new_palette_size as usize;
+        self.colors.resize(size, PaletteColor::default());
+        let first = chunk.first_color_index_to_change as usize;
+        let count = size.saturating_sub(first);
+        for (i, entry) in chunk.palette_entries.iter().take(count).enumerate() {
+            self.set_color(
+                first + i,
+                PaletteColor {
+                    color: entry.color,
+                    name: entry.color_name.clone(),
+                },
+            );
+        }
+    }
+
+    fn apply_packets<I>(&mut self, packets: I)
+    where
+        I: Iterator<Item = (u8, Vec<RGB256>)>,
+    {
+        let mut index = 0;
+        for (skip, colors) in packets {
+            index += skip as usize;
+            for c in colors {
+                let color = RGBA256 {
+                    r: c.r,
+                    g: c.g,
+                    b: c.b,
+                    a: 255,
+                };
+                self.set_color(index, PaletteColor { color, name: None });
+                index += 1;
+            }
+        }
+    }
+
+    /// apply the packets of an Old Palette chunk (0x0004)
+    pub fn apply_old_palette_chunk4(&mut self, chunk: &OldPaletteChunk4) {
+        self.apply_packets(
+            chunk
+                .packets
+                .iter()
+                .map(|p| (p.palette_entries_to_skip, p.colors.clone())),
+        );
+    }
+
+    /// apply the packets of an Old Palette chunk (0x0011), scaling its
+    /// 0-63 components to 0-255
+    pub fn apply_old_palette_chunk11(&mut self, chunk: &OldPaletteChunk11) {
+        let scale = |v: u8| (v << 2) | (v >> 4);
+        self.apply_packets(chunk.packets.iter().map(|p| {
+            let colors = p
+                .colors
+                .iter()
+                .map(|c| RGB256 {
+                    r: scale(c.r),
+                    g: scale(c.g),
+                    b: scale(c.b),
+                })
+                .collect();
+            (p.palette_entries_to_skip, colors)
+        }));
+    }
+}
@@ -0,0 +1,201 @@
+/*
+PNG encoder for decoded images, see https://www.w3.org/TR/png/
+
+Rows are written unfiltered (filter type 0) and zlib compressed in a
+single IDAT chunk.
+*/
+
+use std::io::Write;
+
+use byteorder::{BigEndian, WriteBytesExt};
+use flate2::write::ZlibEncoder;
+use flate2::{Compression, Crc};
+
+use crate::error::{Error, Result};
+use crate::helpers::block_size;
+use crate::{layer_chunk, ColorDepth, Image, Palette, Sprite};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PngOptions<'a> {
+    /// colors of an indexed image, written to the PLTE and tRNS chunks
+    pub palette: Option<&'a Palette>,
+    /// palette index written as fully transparent
+    pub transparent_index: Option<u8>,
+    /// `(pixel_width, pixel_height)` from the header, written to the pHYs
+    /// chunk, 0 means 1:1 like in the header
+    pub pixel_ratio: Option<(u8, u8)>,
+}
+
+fn write_png_chunk<W>(
+    wtr: &mut W,
+    chunk_type: &[u8; 4],
+    data: &[u8],
+) -> Result<()>
+where
+    W: Write,
+{
+    let mut crc = Crc::new();
+    crc.update(chunk_type);
+    crc.update(data);
+
+    // PNG chunk lengths are limited to 31 bits
+    let length = block_size("PNG chunk", 0, data.len())?;
+    if length > i32::MAX as u32 {
+        return Err(Error::invalid_data(format!(
+            "PNG chunk of {} bytes is longer than {} bytes",
+            length,
+            i32::MAX
+        )));
+    }
+    wtr.write_u32::<BigEndian>(length)?;
+    wtr.write_all(chunk_type)?;
+    wtr.write_all(data)?;
+    wtr.write_u32::<BigEndian>(crc.sum())?;
+    Ok(())
+}
+
+/// write `image` as a PNG file, RGBA images are written as 8-bit RGBA,
+/// grayscale images as 8-bit gray and alpha, and indexed images as 8-bit
+/// palette images using `options.palette`
+pub fn write_png<W>(
+    wtr: &mut W,
+    image: &Image,
+    options: &PngOptions,
+) -> Result<()>
+where
+    W: Write,
+{
+    if image.width == 0 || image.height == 0 {
+        return Err(Error::invalid_data("PNG images can not be empty"));
+    }
+
+    let color_type = match image.color_depth {
+        ColorDepth::RGBA => 6,
+        ColorDepth::Grayscale => 4,
+        ColorDepth::Indexed => 3,
+    };
+
+    wtr.write_all(&SIGNATURE)?;
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.write_u32::<BigEndian>(image.width)?;
+    ihdr.write_u32::<BigEndian>(image.height)?;
+    ihdr.write_u8(8)?; // bit depth
+    ihdr.write_u8(color_type)?;
+    ihdr.write_u8(0)?; // compression method
+    ihdr.write_u8(0)?; // filter method
+    ihdr.write_u8(0)?; // interlace method
+    write_png_chunk(wtr, b"IHDR", &ihdr)?;
+
+    if let Some((pixel_width, pixel_height)) = options.pixel_ratio {
+        // pixels per unit, a wide pixel means less pixels along x
+        let (pixel_width, pixel_height) =
+            (pixel_width.max(1), pixel_height.max(1));
+        let mut phys = Vec::with_capacity(9);
+        phys.write_u32::<BigEndian>(pixel_height as u32)?;
+        phys.write_u32::<BigEndian>(pixel_width as u32)?;
+        phys.write_u8(0)?; // unit is unknown, only the aspect ratio
+        write_png_chunk(wtr, b"pHYs", &phys)?;
+    }
+
+    if image.color_depth == ColorDepth::Indexed {
+        let palette = match options.palette {
+            Some(palette) if !palette.is_empty() => palette,
+            _ => {
+                return Err(Error::invalid_data(
+                    "Indexed PNG images need a palette",
+                ));
+            }
+        };
+        let colors = &palette.colors[..palette.len().min(256)];
+
+        let mut plte = Vec::with_capacity(colors.len() * 3);
+        let mut trns = Vec::with_capacity(colors.len());
+        for (i, c) in colors.iter().enumerate() {
+            plte.extend_from_slice(&[c.color.r, c.color.g, c.color.b]);
+            if Some(i as u8) == options.transparent_index {
+                trns.push(0);
+            } else {
+                trns.push(c.color.a);
+            }
+        }
+        write_png_chunk(wtr, b"PLTE", &plte)?;
+
+        let trns_len =
+            trns.iter().rposition(|&a| a != 255).map_or(0, |i| i + 1);
+        if trns_len > 0 {
+            write_png_chunk(wtr, b"tRNS", &trns[..trns_len])?;
+        }
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in image.rows() {
+        encoder.write_all(&[0])?; // filter type
+        encoder.write_all(row)?;
+    }
+    write_png_chunk(wtr, b"IDAT", &encoder.finish()?)?;
+    write_png_chunk(wtr, b"IEND", &[])?;
+    Ok(())
+}
+
+impl Sprite {
+    fn pixel_ratio(&self) -> (u8, u8) {
+        (self.header().pixel_width, self.header().pixel_height)
+    }
+
+    /// render a frame and write it as a RGBA PNG file
+    pub fn write_frame_png<W>(
+        &self,
+        wtr: &mut W,
+        frame_index: usize,
+    ) -> Result<()>
+    where
+        W: Write,
+    {
+        let image = self.render_frame(frame_index)?;
+        let options = PngOptions {
+            pixel_ratio: Some(self.pixel_ratio()),
+            ..PngOptions::default()
+        };
+        write_png(wtr, &image, &options)
+    }
+
+    /// write the image of a cel as a PNG file in the color depth of the
+    /// sprite, linked cels are resolved and indexed cels use the palette
+    /// of their frame, returns false when there's no image to write
+    pub fn write_cel_png<W>(
+        &self,
+        wtr: &mut W,
+        layer_index: usize,
+        frame_index: usize,
+    ) -> Result<bool>
+    where
+        W: Write,
+    {
+        let color_depth = self.header().color_depth;
+        let image = match self.resolve_cel(layer_index, frame_index)? {
+            Some(cel) => match cel.image(&color_depth)? {
+                Some(image) => image,
+                None => return Ok(false),
+            },
+            None => return Ok(false),
+        };
+        let is_background = self.layer(layer_index).is_some_and(|layer| {
+            layer.flags.contains(layer_chunk::Flags::Backgrount)
+        });
+        let palette = self.palette_at(frame_index);
+        let options = PngOptions {
+            palette: Some(&palette),
+            transparent_index: if is_background {
+                None
+            } else {
+                Some(self.header().transparent_palette_entry)
+            },
+            pixel_ratio: Some(self.pixel_ratio()),
+        };
+        write_png(wtr, &image, &options)?;
+        Ok(true)
+    }
+}
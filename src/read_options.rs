@@ -0,0 +1,188 @@
+use crate::error::{Error, Result};
+use crate::{ColorDepth, Header, Image};
+
+/// upper bounds on what a file can make the reader allocate, a file going
+/// over one of them is rejected with `Error::InvalidData`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Limits {
+    /// largest width of the sprite, of a cel and of a tile, tilemap cels
+    /// are measured in tiles
+    pub max_width: u16,
+    /// largest height of the sprite, of a cel and of a tile, tilemap cels
+    /// are measured in tiles
+    pub max_height: u16,
+    pub max_frames: u16,
+    /// most chunks in a single frame
+    pub max_chunks: u32,
+    /// most colors in a palette
+    pub max_palette_size: u32,
+    /// largest cel image, tilemap or tileset image once decompressed,
+    /// in bytes
+    pub max_decompressed_size: u64,
+    /// largest image created, like the RGBA images rendered from the
+    /// sprite, a frame or a sprite sheet, in bytes
+    pub max_image_size: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_width: u16::MAX,
+            max_height: u16::MAX,
+            max_frames: u16::MAX,
+            max_chunks: 1 << 16,
+            max_palette_size: 1 << 16,
+            max_decompressed_size: 256 << 20,
+            max_image_size: 256 << 20,
+        }
+    }
+}
+
+fn check_limit<T>(what: &str, value: T, limit: T) -> Result<()>
+where
+    T: PartialOrd + std::fmt::Display,
+{
+    if value > limit {
+        return Err(Error::invalid_data(format!(
+            "{} is {}, over the limit of {}",
+            what, value, limit
+        )));
+    }
+    Ok(())
+}
+
+impl Limits {
+    /// no limits, for the chunks read outside of a file
+    pub(crate) fn unlimited() -> Self {
+        Limits {
+            max_width: u16::MAX,
+            max_height: u16::MAX,
+            max_frames: u16::MAX,
+            max_chunks: u32::MAX,
+            max_palette_size: u32::MAX,
+            max_decompressed_size: u64::MAX,
+            max_image_size: u64::MAX,
+        }
+    }
+
+    fn check_size(&self, what: &str, width: u16, height: u16) -> Result<()> {
+        check_limit(&format!("{} width", what), width, self.max_width)?;
+        check_limit(&format!("{} height", what), height, self.max_height)
+    }
+
+    fn check_decompressed_size(&self, what: &str, size: u64) -> Result<()> {
+        check_limit(
+            &format!("Decompressed {} size", what),
+            size,
+            self.max_decompressed_size,
+        )
+    }
+
+    /// check the size of an image before allocating it
+    pub(crate) fn check_image_size(
+        &self,
+        what: &str,
+        width: u32,
+        height: u32,
+        color_depth: ColorDepth,
+    ) -> Result<()> {
+        let size = (width as u64 * height as u64)
+            .saturating_mul(color_depth.bytes_per_pixel() as u64);
+        check_limit(&format!("{} size", what), size, self.max_image_size)
+    }
+
+    pub(crate) fn check_header(&self, header: &Header) -> Result<()> {
+        self.check_size(
+            "Sprite",
+            header.width_in_pixels,
+            header.height_in_pixels,
+        )?;
+        self.check_image_size(
+            "Rendered sprite",
+            header.width_in_pixels as u32,
+            header.height_in_pixels as u32,
+            ColorDepth::RGBA,
+        )?;
+        check_limit("Number of frames", header.frames, self.max_frames)
+    }
+
+    pub(crate) fn check_number_of_chunks(&self, chunks: u32) -> Result<()> {
+        check_limit("Number of chunks", chunks, self.max_chunks)
+    }
+
+    /// check the size of a cel as soon as it's read
+    pub(crate) fn check_cel(
+        &self,
+        width: u16,
+        height: u16,
+        color_depth: ColorDepth,
+    ) -> Result<()> {
+        self.check_size("Cel", width, height)?;
+        let size = Image::data_len(width as u32, height as u32, color_depth);
+        self.check_decompressed_size("cel", size as u64)
+    }
+
+    /// check the size of a tilemap as soon as it's read
+    pub(crate) fn check_tilemap(
+        &self,
+        width_in_tiles: u16,
+        height_in_tiles: u16,
+        bits_per_tile: u16,
+    ) -> Result<()> {
+        self.check_size("Tilemap", width_in_tiles, height_in_tiles)?;
+        let size = width_in_tiles as u64
+            * height_in_tiles as u64
+            * (bits_per_tile as u64 / 8);
+        self.check_decompressed_size("tilemap", size)
+    }
+
+    /// check the size of a tileset as soon as it's read
+    pub(crate) fn check_tileset(
+        &self,
+        tile_width: u16,
+        tile_height: u16,
+        number_of_tiles: u32,
+        color_depth: ColorDepth,
+    ) -> Result<()> {
+        self.check_size("Tile", tile_width, tile_height)?;
+        let size = (tile_width as u64
+            * tile_height as u64
+            * color_depth.bytes_per_pixel() as u64)
+            .saturating_mul(number_of_tiles as u64);
+        self.check_decompressed_size("tileset", size)
+    }
+
+    /// check the size of a palette and its number of entries before they
+    /// are read
+    pub(crate) fn check_palette(
+        &self,
+        new_palette_size: u32,
+        number_of_entries: u64,
+    ) -> Result<()> {
+        check_limit("Palette size", new_palette_size, self.max_palette_size)?;
+        check_limit(
+            "Number of palette entries",
+            number_of_entries,
+            self.max_palette_size as u64,
+        )
+    }
+}
+
+/// how the reader handles chunks it can't load
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum ReadMode {
+    /// fail on the first chunk of an unknown type or that can't be read
+    #[default]
+    Strict,
+    /// keep chunks of an unknown type as `ChunkData::Unknown`, skip the
+    /// chunks that can't be read or go over the limits and record why in
+    /// `Aseprite::warnings`
+    Lenient,
+}
+
+/// options of `Aseprite::from_read_with_options`
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct ReadOptions {
+    pub limits: Limits,
+    pub mode: ReadMode,
+}
@@ -0,0 +1,223 @@
+use crate::blend::blend;
+use crate::color::RGBA256;
+use crate::error::{Error, Result};
+use crate::helpers::mul_un8;
+use crate::{
+    layer_chunk, BlendMode, Cel, CelChunk, ColorDepth, Image, LayerChunk,
+    LayerType, Sprite, Tile,
+};
+
+impl Sprite {
+    /// composite all visible layers of a frame into a canvas sized RGBA
+    /// image, reference layers are skipped, a canvas over
+    /// `Limits::max_image_size` is an error
+    pub fn render_frame(&self, frame_index: usize) -> Result<Image> {
+        let mut canvas = Image::new_with_limits(
+            self.width() as u32,
+            self.height() as u32,
+            ColorDepth::RGBA,
+            self.limits(),
+        )?;
+        let color_depth = self.header().color_depth;
+        let tree = self.layer_tree();
+        let palette = self.palette_at(frame_index);
+
+        for layer in self.layers() {
+            if layer.chunk.layer_type == LayerType::Group
+                || layer
+                    .chunk
+                    .flags
+                    .contains(layer_chunk::Flags::ReferenceLayer)
+                || !tree.is_visible(layer.index)
+            {
+                continue;
+            }
+
+            let cel = match self.resolve_cel(layer.index, frame_index)? {
+                Some(cel) => cel,
+                None => continue,
+            };
+            let transparent_index =
+                if layer.chunk.flags.contains(layer_chunk::Flags::Backgrount) {
+                    None
+                } else {
+                    Some(self.header().transparent_palette_entry)
+                };
+            let image = match &cel.cel {
+                Cel::CompressedTilemap { .. } => {
+                    self.tilemap_image(layer.chunk, cel)?
+                }
+                _ => cel.image(&color_depth)?,
+            };
+            let image = match image {
+                Some(image) => image.to_rgba(&palette, transparent_index),
+                None => continue,
+            };
+            let opacity = mul_un8(cel.opacity_level, tree.opacity(layer.index));
+
+            draw_image(
+                &mut canvas,
+                &image,
+                cel.x_position as i32,
+                cel.y_position as i32,
+                layer.chunk.blend_mode,
+                opacity,
+            );
+        }
+
+        Ok(canvas)
+    }
+
+    /// draw the tiles of a tilemap cel from the tileset of its layer, in the
+    /// color depth of the sprite, tiles that aren't in the tileset are left
+    /// empty like Aseprite does
+    pub fn tilemap_image(
+        &self,
+        layer: &LayerChunk,
+        cel: &CelChunk,
+    ) -> Result<Option<Image>> {
+        let tilemap = match cel.cel.tilemap()? {
+            Some(tilemap) => tilemap,
+            None => return Ok(None),
+        };
+        let tileset_id = layer.tileset_index.ok_or_else(|| {
+            Error::invalid_data(format!(
+                "Layer `{}` has a tilemap cel but no tileset",
+                layer.layer_name
+            ))
+        })?;
+        let tileset = self.tileset(tileset_id).ok_or_else(|| {
+            Error::invalid_data(format!("Tileset {} not found", tileset_id))
+        })?;
+        let color_depth = self.header().color_depth;
+        let tiles = tileset.image(&color_depth)?.ok_or_else(|| {
+            Error::invalid_data(format!(
+                "Tiles of tileset {} are not stored in the file",
+                tileset_id
+            ))
+        })?;
+
+        let tile_width = tileset.tile_width as u32;
+        let tile_height = tileset.tile_height as u32;
+        let width = tilemap.width as u32 * tile_width;
+        let height = tilemap.height as u32 * tile_height;
+        let mut image =
+            Image::new_with_limits(width, height, color_depth, self.limits())?;
+        for ty in 0..tilemap.height {
+            for tx in 0..tilemap.width {
+                let tile = match tilemap.get(tx, ty) {
+                    Some(tile) if tile.tile_id < tileset.number_of_tiles => {
+                        tile
+                    }
+                    _ => continue,
+                };
+                for y in 0..tile_height {
+                    for x in 0..tile_width {
+                        let (sx, sy) = match tile_source(
+                            tile,
+                            x,
+                            y,
+                            tile_width,
+                            tile_height,
+                        ) {
+                            Some(source) => source,
+                            None => continue,
+                        };
+                        let pixel = match tiles
+                            .get(sx, tile.tile_id * tile_height + sy)
+                        {
+                            Some(pixel) => pixel,
+                            None => continue,
+                        };
+                        image.set(
+                            tx as u32 * tile_width + x,
+                            ty as u32 * tile_height + y,
+                            pixel,
+                        );
+                    }
+                }
+            }
+        }
+        Ok(Some(image))
+    }
+
+    /// `render_frame` with the colors converted from the color profile of
+    /// the sprite to sRGB
+    pub fn render_frame_srgb(&self, frame_index: usize) -> Result<Image> {
+        let mut image = self.render_frame(frame_index)?;
+        if let Some(profile) = self.color_profile() {
+            profile.srgb_transform()?.convert_image(&mut image);
+        }
+        Ok(image)
+    }
+}
+
+/// position in the tile image of the pixel drawn at `x`, `y` of a flipped
+/// tile, the diagonal flip swaps the axes before the other flips, so parts
+/// of a flipped tile that isn't square have no source
+fn tile_source(
+    tile: &Tile,
+    x: u32,
+    y: u32,
+    tile_width: u32,
+    tile_height: u32,
+) -> Option<(u32, u32)> {
+    let x = if tile.x_flip { tile_width - 1 - x } else { x };
+    let y = if tile.y_flip { tile_height - 1 - y } else { y };
+    let (x, y) = if tile.diagonal_flip { (y, x) } else { (x, y) };
+    if x < tile_width && y < tile_height {
+        Some((x, y))
+    } else {
+        None
+    }
+}
+
+/// blend a RGBA `image` into a RGBA `canvas` at the given position
+fn draw_image(
+    canvas: &mut Image,
+    image: &Image,
+    x: i32,
+    y: i32,
+    blend_mode: BlendMode,
+    opacity: u8,
+) {
+    if opacity == 0 {
+        return;
+    }
+
+    let canvas_width = canvas.width as i32;
+    let canvas_height = canvas.height as i32;
+    for iy in 0..image.height as i32 {
+        let cy = y + iy;
+        if cy < 0 || cy >= canvas_height {
+            continue;
+        }
+        let src_row = image.row(iy as u32).unwrap_or(&[]);
+        let dst_row = match canvas.row_mut(cy as u32) {
+            Some(row) => row,
+            None => continue,
+        };
+        for ix in 0..image.width as i32 {
+            let cx = x + ix;
+            if cx < 0 || cx >= canvas_width {
+                continue;
+            }
+            let s = &src_row[ix as usize * 4..ix as usize * 4 + 4];
+            let d = &mut dst_row[cx as usize * 4..cx as usize * 4 + 4];
+            let src = RGBA256 {
+                r: s[0],
+                g: s[1],
+                b: s[2],
+                a: s[3],
+            };
+            let backdrop = RGBA256 {
+                r: d[0],
+                g: d[1],
+                b: d[2],
+                a: d[3],
+            };
+            let c = blend(blend_mode, backdrop, src, opacity);
+            d.copy_from_slice(&[c.r, c.g, c.b, c.a]);
+        }
+    }
+}
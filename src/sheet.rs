@@ -0,0 +1,755 @@
+/*
+Sprite sheets laid out like the ones exported by the Aseprite CLI, with the
+JSON data file in its hash or array flavor:
+
+{ "frames": {
+   "sprite 0.aseprite": {
+    "frame": { "x": 0, "y": 0, "w": 32, "h": 32 },
+    "rotated": false,
+    "trimmed": false,
+    "spriteSourceSize": { "x": 0, "y": 0, "w": 32, "h": 32 },
+    "sourceSize": { "w": 32, "h": 32 },
+    "duration": 100
+   }
+ },
+ "meta": { "app", "version", "image", "format", "size", "scale",
+           "frameTags", "slices" }
+}
+*/
+
+use std::cmp::Reverse;
+use std::convert::TryFrom;
+use std::fmt::Write as _;
+use std::io::Write;
+
+use crate::color::RGBA256;
+use crate::error::{Error, Result};
+use crate::png::{write_png, PngOptions};
+use crate::{ColorDepth, Image, LoopAnimationDirection, Sprite};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SheetType {
+    /// all frames in a single row
+    Horizontal,
+    /// all frames in a single column
+    Vertical,
+    /// frames placed row by row
+    Rows,
+    /// frames placed column by column
+    Columns,
+    /// frames placed row by row in the grid with the smallest area, frames
+    /// aren't trimmed, see `Packed`
+    SmallestGrid,
+    /// frames trimmed to their non-transparent pixels and packed in the
+    /// smallest sheet found, like Aseprite's packed sheets of trimmed cels
+    Packed,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum JsonFormat {
+    /// `frames` is an object keyed by the frame filename
+    Hash,
+    /// `frames` is an array with a `filename` field in each frame
+    Array,
+}
+
+#[derive(Debug, Clone)]
+pub struct SheetOptions {
+    pub sheet_type: SheetType,
+    /// frames per row for `SheetType::Rows`, 0 makes the sheet square
+    pub columns: u32,
+    /// frames per column for `SheetType::Columns`, 0 makes the sheet square
+    pub rows: u32,
+    /// space around the whole sheet
+    pub border_padding: u32,
+    /// space between frames
+    pub shape_padding: u32,
+    /// space around each frame, inside its frame rect
+    pub inner_padding: u32,
+    /// repeat the edge pixels of each frame one pixel outside its frame rect
+    pub extrude: bool,
+    /// name of the sprite file, frames are named "{title} {frame}.{ext}"
+    pub filename: String,
+    /// name of the sheet image written in `meta.image`
+    pub image: String,
+}
+
+impl Default for SheetOptions {
+    fn default() -> Self {
+        Self {
+            sheet_type: SheetType::Horizontal,
+            columns: 0,
+            rows: 0,
+            border_padding: 0,
+            shape_padding: 0,
+            inner_padding: 0,
+            extrude: false,
+            filename: "sprite.aseprite".to_owned(),
+            image: "sprite.png".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SheetFrame {
+    pub frame_index: usize,
+    pub filename: String,
+    /// frame rect in the sheet, including the inner padding
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// whether the transparent edges of the frame were trimmed
+    pub trimmed: bool,
+    /// rect of the frame pixels in the sprite, the whole sprite unless the
+    /// frame is trimmed
+    pub source_x: u32,
+    pub source_y: u32,
+    pub source_width: u32,
+    pub source_height: u32,
+    pub duration_milliseconds: u16,
+}
+
+/// rendered frames of a sprite packed into a single RGBA image
+#[derive(Debug)]
+pub struct SpriteSheet<'a> {
+    sprite: &'a Sprite,
+    options: SheetOptions,
+    pub image: Image,
+    pub frames: Vec<SheetFrame>,
+}
+
+fn square_side(count: u32) -> u32 {
+    let mut side: u32 = 1;
+    while (side as u64 * side as u64) < count as u64 {
+        side += 1;
+    }
+    side
+}
+
+fn too_large() -> Error {
+    Error::invalid_data("Sprite sheet size doesn't fit in 32 bits")
+}
+
+/// width and height of a sheet, with the position of each frame rect
+type Layout = (u32, u32, Vec<(u32, u32)>);
+
+/// width or height of a sheet with `cells` cells of `size` pixels
+fn sheet_side(options: &SheetOptions, cells: u32, size: u32) -> Result<u32> {
+    cells
+        .checked_mul(size)
+        .and_then(|side| {
+            side.checked_add(
+                cells.saturating_sub(1).checked_mul(options.shape_padding)?,
+            )
+        })
+        .and_then(|side| {
+            side.checked_add(options.border_padding.checked_mul(2)?)
+        })
+        .ok_or_else(too_large)
+}
+
+/// (columns, rows) of the grid holding `count` cells
+fn grid_size(
+    options: &SheetOptions,
+    count: u32,
+    cell: (u32, u32),
+) -> (u32, u32) {
+    let count = count.max(1);
+    let div_ceil = |a: u32, b: u32| a.div_ceil(b.max(1));
+    match options.sheet_type {
+        SheetType::Horizontal => (count, 1),
+        SheetType::Vertical => (1, count),
+        SheetType::Rows => {
+            let columns = match options.columns {
+                0 => square_side(count),
+                columns => columns.min(count),
+            };
+            (columns, div_ceil(count, columns))
+        }
+        SheetType::Columns => {
+            let rows = match options.rows {
+                0 => square_side(count),
+                rows => rows.min(count),
+            };
+            (div_ceil(count, rows), rows)
+        }
+        SheetType::SmallestGrid => {
+            // sizes too large for the sheet sort last
+            let side = |cells: u32, size: u32| {
+                sheet_side(options, cells, size).map_or(u64::MAX, u64::from)
+            };
+            (1..=count)
+                .map(|columns| (columns, div_ceil(count, columns)))
+                .min_by_key(|&(columns, rows)| {
+                    let width = side(columns, cell.0);
+                    let height = side(rows, cell.1);
+                    (width.saturating_mul(height), width.max(height))
+                })
+                .unwrap_or((count, 1))
+        }
+        SheetType::Packed => (count, 1),
+    }
+}
+
+/// positions of the `count` frame rects of `size` in the grid laid out by
+/// `options`, with the size of the sheet
+fn grid_layout(
+    options: &SheetOptions,
+    count: u32,
+    size: (u32, u32),
+    extrude_size: u32,
+) -> Result<Layout> {
+    // the cell of a frame holds its extrusion
+    let cell = (
+        size.0.checked_add(2 * extrude_size).ok_or_else(too_large)?,
+        size.1.checked_add(2 * extrude_size).ok_or_else(too_large)?,
+    );
+    let (columns, rows) = grid_size(options, count, cell);
+    if count == 0 {
+        return Ok((0, 0, vec![]));
+    }
+    let width = sheet_side(options, columns, cell.0)?;
+    let height = sheet_side(options, rows, cell.1)?;
+
+    // positions are inside the sheet, so they fit in a u32 too
+    let positions = (0..count)
+        .map(|i| {
+            let (column, row) = match options.sheet_type {
+                SheetType::Vertical | SheetType::Columns => {
+                    (i / rows, i % rows)
+                }
+                _ => (i % columns, i / columns),
+            };
+            let x = options.border_padding
+                + column * cell.0
+                + column * options.shape_padding
+                + extrude_size;
+            let y = options.border_padding
+                + row * cell.1
+                + row * options.shape_padding
+                + extrude_size;
+            (x, y)
+        })
+        .collect();
+    Ok((width, height, positions))
+}
+
+/// place the rects of `sizes` in the given order in a sheet `max_width`
+/// wide, each on the lowest spot of the skyline left by the rects before
+/// it, returns their positions with the width and height used
+fn skyline_pack(
+    sizes: &[(u64, u64)],
+    order: &[usize],
+    max_width: u64,
+) -> (Vec<(u64, u64)>, u64, u64) {
+    // (x, y, width) of the skyline segments, from left to right
+    let mut skyline = vec![(0, 0, max_width)];
+    let mut positions = vec![(0, 0); sizes.len()];
+    let (mut width, mut height) = (0, 0);
+    for &i in order {
+        let (w, h) = sizes[i];
+        // the rect always fits on the first segment, at x = 0
+        let mut best = (u64::MAX, 0, 0);
+        for start in 0..skyline.len() {
+            let x = skyline[start].0;
+            if x + w > max_width {
+                break;
+            }
+            let mut y = 0;
+            let mut covered = 0;
+            for &(_, segment_y, segment_width) in &skyline[start..] {
+                y = y.max(segment_y);
+                covered += segment_width;
+                if covered >= w {
+                    break;
+                }
+            }
+            if y < best.0 {
+                best = (y, x, start);
+            }
+        }
+        let (y, x, start) = best;
+        positions[i] = (x, y);
+        width = width.max(x + w);
+        height = height.max(y + h);
+
+        // raise the skyline under the rect
+        let right = x + w;
+        let covered = skyline.split_off(start);
+        skyline.push((x, y + h, w));
+        for (segment_x, segment_y, segment_width) in covered {
+            let segment_right = segment_x + segment_width;
+            if segment_right > right {
+                let segment_x = segment_x.max(right);
+                skyline.push((segment_x, segment_y, segment_right - segment_x));
+            }
+        }
+        skyline.dedup_by(|next, previous| {
+            let same_height = next.1 == previous.1;
+            if same_height {
+                previous.2 += next.2;
+            }
+            same_height
+        });
+    }
+    (positions, width, height)
+}
+
+/// pack the rects of `sizes` trying sheet widths from the widest rect to
+/// all the rects side by side, keeping the smallest area and then the
+/// squarest sheet
+fn pack(sizes: &[(u64, u64)]) -> (Vec<(u64, u64)>, u64, u64) {
+    // tallest rects first, equal rects stay in frame order
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| (Reverse(sizes[i].1), Reverse(sizes[i].0)));
+    let widest = sizes.iter().map(|size| size.0).max().unwrap_or(0);
+    let mut widths: Vec<u64> = order
+        .iter()
+        .scan(0, |sum, &i| {
+            *sum += sizes[i].0;
+            Some((*sum).max(widest))
+        })
+        .collect();
+    widths.dedup();
+    // a few dozen widths are enough for sprites with many frames
+    let step = (widths.len() / 64).max(1);
+    let last = widths.last().copied();
+    widths
+        .into_iter()
+        .step_by(step)
+        .chain(last)
+        .map(|width| skyline_pack(sizes, &order, width))
+        .min_by_key(|&(_, width, height)| {
+            (width.saturating_mul(height), width.max(height))
+        })
+        .unwrap_or_default()
+}
+
+/// positions of the frame rects of `sizes` packed in the sheet, with the
+/// size of the sheet
+fn packed_layout(
+    options: &SheetOptions,
+    sizes: &[(u32, u32)],
+    extrude_size: u32,
+) -> Result<Layout> {
+    if sizes.is_empty() {
+        return Ok((0, 0, vec![]));
+    }
+    // each rect holds its extrusion and the space to the next rect
+    let padding = options.shape_padding as u64;
+    let extrude_size = extrude_size as u64;
+    let rects: Vec<_> = sizes
+        .iter()
+        .map(|&(width, height)| {
+            (
+                width as u64 + 2 * extrude_size + padding,
+                height as u64 + 2 * extrude_size + padding,
+            )
+        })
+        .collect();
+    let (positions, width, height) = pack(&rects);
+
+    let border = options.border_padding as u64;
+    let side = |used: u64| {
+        u32::try_from(used - padding + 2 * border).map_err(|_| too_large())
+    };
+    let (width, height) = (side(width)?, side(height)?);
+    // positions are inside the sheet, so they fit in a u32 too
+    let positions = positions
+        .into_iter()
+        .map(|(x, y)| {
+            (
+                (border + x + extrude_size) as u32,
+                (border + y + extrude_size) as u32,
+            )
+        })
+        .collect();
+    Ok((width, height, positions))
+}
+
+/// rect of the non-transparent pixels of a RGBA image, a transparent image
+/// keeps its top left pixel
+fn trim_bounds(image: &Image) -> (u32, u32, u32, u32) {
+    // (left, top, right, bottom) of the pixels found so far
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for (y, row) in image.rows().enumerate() {
+        let y = y as u32;
+        for (x, pixel) in row.chunks_exact(4).enumerate() {
+            if pixel[3] == 0 {
+                continue;
+            }
+            let x = x as u32;
+            bounds = Some(match bounds {
+                None => (x, y, x, y),
+                Some((left, top, right, _)) => {
+                    (left.min(x), top, right.max(x), y)
+                }
+            });
+        }
+    }
+    match bounds {
+        Some((left, top, right, bottom)) => {
+            (left, top, right - left + 1, bottom - top + 1)
+        }
+        None => (0, 0, image.width.min(1), image.height.min(1)),
+    }
+}
+
+/// copy the `source` rect of `image` into `sheet` at the given position
+fn blit(
+    sheet: &mut Image,
+    image: &Image,
+    source: (u32, u32, u32, u32),
+    x: u32,
+    y: u32,
+) {
+    let (source_x, source_y, width, height) = source;
+    for iy in 0..height {
+        let row = match image.row(source_y + iy) {
+            Some(row) => row,
+            None => break,
+        };
+        let row = &row[source_x as usize * 4..(source_x + width) as usize * 4];
+        if let Some(dst) = sheet.row_mut(y + iy) {
+            let start = x as usize * 4;
+            dst[start..start + row.len()].copy_from_slice(row);
+        }
+    }
+}
+
+/// repeat the edge pixels of the rect one pixel outwards
+fn extrude(sheet: &mut Image, x: u32, y: u32, width: u32, height: u32) {
+    let mut copy = |sx: u32, sy: u32, dx: u32, dy: u32| {
+        if let Some(pixel) = sheet.get(sx, sy) {
+            sheet.set(dx, dy, pixel);
+        }
+    };
+    for ix in x..x + width {
+        copy(ix, y, ix, y - 1);
+        copy(ix, y + height - 1, ix, y + height);
+    }
+    for iy in y - 1..y + height + 1 {
+        copy(x, iy, x - 1, iy);
+        copy(x + width - 1, iy, x + width, iy);
+    }
+}
+
+impl Sprite {
+    /// render every frame into a sprite sheet laid out by `options`
+    pub fn sprite_sheet(
+        &self,
+        options: &SheetOptions,
+    ) -> Result<SpriteSheet<'_>> {
+        let frame_count =
+            u32::try_from(self.frame_count()).map_err(|_| too_large())?;
+        let extrude_size = if options.extrude { 1 } else { 0 };
+        // size of a frame with its padding
+        let padded = |size: u32| {
+            options
+                .inner_padding
+                .checked_mul(2)
+                .and_then(|padding| padding.checked_add(size))
+                .ok_or_else(too_large)
+        };
+        let whole = (0, 0, self.width() as u32, self.height() as u32);
+        // rect of the sprite copied for each frame
+        let sources = match options.sheet_type {
+            SheetType::Packed => self
+                .frames()
+                .map(|info| Ok(trim_bounds(&self.render_frame(info.index)?)))
+                .collect::<Result<Vec<_>>>()?,
+            _ => vec![whole; frame_count as usize],
+        };
+        let (width, height, positions) = match options.sheet_type {
+            SheetType::Packed => {
+                let sizes = sources
+                    .iter()
+                    .map(|source| Ok((padded(source.2)?, padded(source.3)?)))
+                    .collect::<Result<Vec<_>>>()?;
+                packed_layout(options, &sizes, extrude_size)?
+            }
+            _ => grid_layout(
+                options,
+                frame_count,
+                (padded(whole.2)?, padded(whole.3)?),
+                extrude_size,
+            )?,
+        };
+        let mut image = Image::new_with_limits(
+            width,
+            height,
+            ColorDepth::RGBA,
+            self.limits(),
+        )?;
+
+        let (title, extension) = match options.filename.rfind('.') {
+            Some(i) => (&options.filename[..i], &options.filename[i..]),
+            None => (options.filename.as_str(), ""),
+        };
+
+        let mut frames = Vec::with_capacity(frame_count as usize);
+        for ((info, source), (x, y)) in
+            self.frames().zip(sources).zip(positions)
+        {
+            let frame_width = padded(source.2)?;
+            let frame_height = padded(source.3)?;
+            let rendered = self.render_frame(info.index)?;
+            blit(
+                &mut image,
+                &rendered,
+                source,
+                x + options.inner_padding,
+                y + options.inner_padding,
+            );
+            if options.extrude {
+                extrude(&mut image, x, y, frame_width, frame_height);
+            }
+
+            let filename = if frame_count == 1 {
+                options.filename.clone()
+            } else {
+                format!("{} {}{}", title, info.index, extension)
+            };
+            frames.push(SheetFrame {
+                frame_index: info.index,
+                filename,
+                x,
+                y,
+                width: frame_width,
+                height: frame_height,
+                trimmed: source != whole,
+                source_x: source.0,
+                source_y: source.1,
+                source_width: source.2,
+                source_height: source.3,
+                duration_milliseconds: info.duration_milliseconds,
+            });
+        }
+
+        Ok(SpriteSheet {
+            sprite: self,
+            options: options.clone(),
+            image,
+            frames,
+        })
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// array with one item per line, like in Aseprite's data files
+fn json_list(items: &[String]) -> String {
+    if items.is_empty() {
+        "[\n  ]".to_owned()
+    } else {
+        format!("[\n{}\n  ]", items.join(",\n"))
+    }
+}
+
+fn json_color(c: RGBA256) -> String {
+    format!("\"#{:02x}{:02x}{:02x}{:02x}\"", c.r, c.g, c.b, c.a)
+}
+
+impl SpriteSheet<'_> {
+    /// write the sheet image as a PNG file
+    pub fn write_png<W>(&self, wtr: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        write_png(wtr, &self.image, &PngOptions::default())
+    }
+
+    /// JSON data file describing the frames, tags and slices of the sheet
+    pub fn json(&self, format: JsonFormat) -> String {
+        let mut json = String::new();
+        let (open, close) = match format {
+            JsonFormat::Hash => ('{', '}'),
+            JsonFormat::Array => ('[', ']'),
+        };
+        let (sprite_width, sprite_height) =
+            (self.sprite.width(), self.sprite.height());
+
+        // writing to a String can't fail
+        let _ = writeln!(json, "{{ \"frames\": {}", open);
+        for (i, frame) in self.frames.iter().enumerate() {
+            let _ = match format {
+                JsonFormat::Hash => {
+                    writeln!(json, "   {}: {{", json_string(&frame.filename))
+                }
+                JsonFormat::Array => write!(
+                    json,
+                    "   {{\n    \"filename\": {},\n",
+                    json_string(&frame.filename)
+                ),
+            };
+            let _ = write!(
+                json,
+                "    \"frame\": {{ \"x\": {}, \"y\": {}, \"w\": {}, \"h\": {} }},\n\
+                 \x20   \"rotated\": false,\n\
+                 \x20   \"trimmed\": {},\n\
+                 \x20   \"spriteSourceSize\": {{ \"x\": {}, \"y\": {}, \"w\": {}, \"h\": {} }},\n\
+                 \x20   \"sourceSize\": {{ \"w\": {}, \"h\": {} }},\n\
+                 \x20   \"duration\": {}\n   }}",
+                frame.x,
+                frame.y,
+                frame.width,
+                frame.height,
+                frame.trimmed,
+                frame.source_x,
+                frame.source_y,
+                frame.source_width,
+                frame.source_height,
+                sprite_width,
+                sprite_height,
+                frame.duration_milliseconds,
+            );
+            json.push_str(if i + 1 < self.frames.len() {
+                ",\n"
+            } else {
+                "\n"
+            });
+        }
+        let _ = writeln!(json, " {},", close);
+
+        let _ = write!(
+            json,
+            " \"meta\": {{\n\
+             \x20 \"app\": \"https://www.aseprite.org/\",\n\
+             \x20 \"version\": \"1.3\",\n\
+             \x20 \"image\": {},\n\
+             \x20 \"format\": \"RGBA8888\",\n\
+             \x20 \"size\": {{ \"w\": {}, \"h\": {} }},\n\
+             \x20 \"scale\": \"1\",\n",
+            json_string(&self.options.image),
+            self.image.width,
+            self.image.height,
+        );
+
+        let tags: Vec<_> = self
+            .sprite
+            .tags()
+            .map(|tag| {
+                let direction = match tag.loop_animation_direction {
+                    LoopAnimationDirection::Forward => "forward",
+                    LoopAnimationDirection::Reverse => "reverse",
+                    LoopAnimationDirection::PingPong => "pingpong",
+                };
+                let color =
+                    tag.user_data().and_then(|u| u.color).unwrap_or(RGBA256 {
+                        r: tag.tag_color.r,
+                        g: tag.tag_color.g,
+                        b: tag.tag_color.b,
+                        a: 255,
+                    });
+                let mut entry = format!(
+                    "   {{ \"name\": {}, \"from\": {}, \"to\": {}, \
+                     \"direction\": \"{}\", \"color\": {}",
+                    json_string(&tag.tag_name),
+                    tag.from_tag,
+                    tag.to_tag,
+                    direction,
+                    json_color(color),
+                );
+                if let Some(text) =
+                    tag.user_data().and_then(|u| u.text.as_ref())
+                {
+                    let _ = write!(entry, ", \"data\": {}", json_string(text));
+                }
+                entry.push_str(" }");
+                entry
+            })
+            .collect();
+        let _ = writeln!(json, "  \"frameTags\": {},", json_list(&tags));
+
+        let slices: Vec<_> = self
+            .sprite
+            .slices()
+            .map(|slice| {
+                let color = slice.user_data().and_then(|u| u.color).unwrap_or(
+                    RGBA256 {
+                        r: 0,
+                        g: 0,
+                        b: 255,
+                        a: 255,
+                    },
+                );
+                let mut entry = format!(
+                    "   {{ \"name\": {}, \"color\": {}",
+                    json_string(&slice.name),
+                    json_color(color),
+                );
+                if let Some(text) =
+                    slice.user_data().and_then(|u| u.text.as_ref())
+                {
+                    let _ = write!(entry, ", \"data\": {}", json_string(text));
+                }
+                let keys: Vec<_> = slice
+                    .keys
+                    .iter()
+                    .map(|key| {
+                        let mut key_json = format!(
+                            "{{ \"frame\": {}, \"bounds\": {{\"x\": {}, \"y\": {}, \"w\": {}, \"h\": {} }}",
+                            key.frame_number,
+                            key.x_origin,
+                            key.y_origin,
+                            key.width,
+                            key.height,
+                        );
+                        if let Some(center) = &key.nine_patches_info {
+                            let _ = write!(
+                                key_json,
+                                ", \"center\": {{\"x\": {}, \"y\": {}, \"w\": {}, \"h\": {} }}",
+                                center.x_position,
+                                center.y_position,
+                                center.width,
+                                center.height,
+                            );
+                        }
+                        if let Some(pivot) = &key.pivot_info {
+                            let _ = write!(
+                                key_json,
+                                ", \"pivot\": {{\"x\": {}, \"y\": {} }}",
+                                pivot.x_position, pivot.y_position,
+                            );
+                        }
+                        key_json.push_str(" }");
+                        key_json
+                    })
+                    .collect();
+                let _ = write!(entry, ", \"keys\": [{}] }}", keys.join(", "));
+                entry
+            })
+            .collect();
+        let _ = write!(json, "  \"slices\": {}\n }}\n}}\n", json_list(&slices));
+
+        json
+    }
+
+    /// write the JSON data file of the sheet
+    pub fn write_json<W>(&self, wtr: &mut W, format: JsonFormat) -> Result<()>
+    where
+        W: Write,
+    {
+        wtr.write_all(self.json(format).as_bytes())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+use crate::{
+    Aseprite, Cel, CelChunk, ChunkData, ColorProfileChunk, Header, LayerChunk,
+    LayerTree, Limits, Palette, PaletteChunk, SliceChunk, Tag, TilesetChunk,
+    UserDataChunk,
+};
+
+/// position of a chunk inside `Aseprite::frames`
+#[derive(Debug, Clone, Copy)]
+struct ChunkIndex {
+    frame: usize,
+    chunk: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Layer<'a> {
+    pub index: usize,
+    pub chunk: &'a LayerChunk,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct FrameInfo {
+    pub index: usize,
+    pub duration_milliseconds: u16,
+}
+
+/// document view over an `Aseprite`, resolving which chunks are the
+/// layers, cels, tags, slices and palette of the sprite
+#[derive(Debug)]
+pub struct Sprite {
+    ase: Aseprite,
+    layers: Vec<ChunkIndex>,
+    cels: HashMap<(usize, usize), ChunkIndex>,
+    tags: Vec<(ChunkIndex, usize)>,
+    slices: Vec<ChunkIndex>,
+    tilesets: Vec<ChunkIndex>,
+    palette: Option<ChunkIndex>,
+    color_profile: Option<ChunkIndex>,
+    limits: Limits,
+}
+
+impl Sprite {
+    pub fn from_aseprite(ase: Aseprite) -> Self {
+        Sprite::from_aseprite_with_limits(ase, Limits::default())
+    }
+
+    /// `limits.max_image_size` bounds the images rendered from the sprite
+    pub fn from_aseprite_with_limits(ase: Aseprite, limits: Limits) -> Self {
+        let mut layers = Vec::new();
+        let mut cels = HashMap::new();
+        let mut tags = Vec::new();
+        let mut slices = Vec::new();
+        let mut tilesets = Vec::new();
+        let mut palette = None;
+        let mut color_profile = None;
+
+        for (frame_index, frame) in ase.frames.iter().enumerate() {
+            for (chunk_index, chunk) in frame.chunks.iter().enumerate() {
+                let index = ChunkIndex {
+                    frame: frame_index,
+                    chunk: chunk_index,
+                };
+                match &chunk.chunk_data {
+                    ChunkData::LayerChunk(_) => layers.push(index),
+                    ChunkData::CelChunk(cel) => {
+                        cels.insert(
+                            (cel.layer_index as usize, frame_index),
+                            index,
+                        );
+                    }
+                    ChunkData::FrameTagsChunk(frame_tags) => {
+                        tags.extend(
+                            (0..frame_tags.tags.len()).map(|i| (index, i)),
+                        );
+                    }
+                    ChunkData::SliceChunk(_) => slices.push(index),
+                    ChunkData::TilesetChunk(_) => tilesets.push(index),
+                    ChunkData::PaletteChunk(_) if palette.is_none() => {
+                        palette = Some(index);
+                    }
+                    ChunkData::ColorProfileChunk(_)
+                        if color_profile.is_none() =>
+                    {
+                        color_profile = Some(index);
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        Self {
+            ase,
+            layers,
+            cels,
+            tags,
+            slices,
+            tilesets,
+            palette,
+            color_profile,
+            limits,
+        }
+    }
+
+    pub fn into_aseprite(self) -> Aseprite {
+        self.ase
+    }
+
+    pub fn aseprite(&self) -> &Aseprite {
+        &self.ase
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.ase.header
+    }
+
+    pub fn width(&self) -> u16 {
+        self.ase.header.width_in_pixels
+    }
+
+    pub fn height(&self) -> u16 {
+        self.ase.header.height_in_pixels
+    }
+
+    /// User Data chunk of the sprite
+    pub fn user_data(&self) -> Option<&UserDataChunk> {
+        self.ase.user_data()
+    }
+
+    fn chunk_data(&self, index: ChunkIndex) -> &ChunkData {
+        &self.ase.frames[index.frame].chunks[index.chunk].chunk_data
+    }
+
+    /// layers in file order, from the bottom to the top of the stack
+    pub fn layers(&self) -> impl Iterator<Item = Layer<'_>> + '_ {
+        self.layers
+            .iter()
+            .enumerate()
+            .filter_map(move |(i, &index)| match self.chunk_data(index) {
+                ChunkData::LayerChunk(chunk) => Some(Layer { index: i, chunk }),
+                _ => None,
+            })
+    }
+
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn layer(&self, layer_index: usize) -> Option<&LayerChunk> {
+        match self.chunk_data(*self.layers.get(layer_index)?) {
+            ChunkData::LayerChunk(chunk) => Some(chunk),
+            _ => None,
+        }
+    }
+
+    /// find the first layer named `name`
+    pub fn layer_by_name(&self, name: &str) -> Option<Layer<'_>> {
+        self.layers().find(|layer| layer.chunk.layer_name == name)
+    }
+
+    /// group hierarchy of the layers
+    pub fn layer_tree(&self) -> LayerTree {
+        let layers: Vec<_> = self.layers().map(|layer| layer.chunk).collect();
+        LayerTree::new(&layers, &self.ase.header)
+    }
+
+    /// find a layer by its group path, like "Characters/Hero/Body"
+    pub fn layer_by_path(&self, path: &str) -> Option<Layer<'_>> {
+        let index = self.layer_tree().find(path)?;
+        self.layers().nth(index)
+    }
+
+    pub fn frames(&self) -> impl Iterator<Item = FrameInfo> + '_ {
+        self.ase
+            .frames
+            .iter()
+            .enumerate()
+            .map(|(index, frame)| FrameInfo {
+                index,
+                duration_milliseconds: frame.frame_duration_milliseconds,
+            })
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.ase.frames.len()
+    }
+
+    /// cel of `layer_index` at `frame_index`, as stored in the file
+    pub fn cel(
+        &self,
+        layer_index: usize,
+        frame_index: usize,
+    ) -> Option<&CelChunk> {
+        match self.chunk_data(*self.cels.get(&(layer_index, frame_index))?) {
+            ChunkData::CelChunk(chunk) => Some(chunk),
+            _ => None,
+        }
+    }
+
+    /// cel shown for `layer_index` at `frame_index`, following linked cels
+    /// to the cel they link to, whose position, opacity and pixels apply
+    pub fn resolve_cel(
+        &self,
+        layer_index: usize,
+        frame_index: usize,
+    ) -> Result<Option<&CelChunk>> {
+        let mut cel = match self.cel(layer_index, frame_index) {
+            Some(cel) => cel,
+            None => return Ok(None),
+        };
+
+        // a layer has a cel in at most every frame, a walk through more links
+        // than that went around a cycle
+        let mut links = 0;
+        while let Cel::LinkedCel { frame_position } = cel.cel {
+            let frame_position = frame_position as usize;
+            links += 1;
+            if links > self.frame_count() {
+                return Err(Error::invalid_data(format!(
+                    "Cyclic Linked Cel in layer {}: frame {} never links to \
+                     a cel with pixels",
+                    layer_index, frame_index
+                )));
+            }
+
+            cel = match self.cel(layer_index, frame_position) {
+                Some(cel) => cel,
+                None => {
+                    return Err(Error::invalid_data(format!(
+                        "Broken Linked Cel in layer {}: frame {} has no cel",
+                        layer_index, frame_position
+                    )));
+                }
+            };
+        }
+
+        Ok(Some(cel))
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = &Tag> + '_ {
+        self.tags.iter().filter_map(move |&(index, i)| {
+            match self.chunk_data(index) {
+                ChunkData::FrameTagsChunk(chunk) => chunk.tags.get(i),
+                _ => None,
+            }
+        })
+    }
+
+    /// find the first tag named `name`
+    pub fn tag(&self, name: &str) -> Option<&Tag> {
+        self.tags().find(|tag| tag.tag_name == name)
+    }
+
+    pub fn slices(&self) -> impl Iterator<Item = &SliceChunk> + '_ {
+        self.slices.iter().filter_map(move |&index| {
+            match self.chunk_data(index) {
+                ChunkData::SliceChunk(chunk) => Some(chunk),
+                _ => None,
+            }
+        })
+    }
+
+    /// find the first slice named `name`
+    pub fn slice(&self, name: &str) -> Option<&SliceChunk> {
+        self.slices().find(|slice| slice.name == name)
+    }
+
+    pub fn tilesets(&self) -> impl Iterator<Item = &TilesetChunk> + '_ {
+        self.tilesets.iter().filter_map(move |&index| {
+            match self.chunk_data(index) {
+                ChunkData::TilesetChunk(chunk) => Some(chunk),
+                _ => None,
+            }
+        })
+    }
+
+    /// find the tileset a tilemap layer refers to with its `tileset_index`
+    pub fn tileset(&self, tileset_id: u32) -> Option<&TilesetChunk> {
+        self.tilesets()
+            .find(|tileset| tileset.tileset_id == tileset_id)
+    }
+
+    /// palette chunk of the sprite, the first one in the file
+    pub fn palette(&self) -> Option<&PaletteChunk> {
+        match self.chunk_data(self.palette?) {
+            ChunkData::PaletteChunk(chunk) => Some(chunk),
+            _ => None,
+        }
+    }
+
+    /// color profile chunk of the sprite, the first one in the file
+    pub fn color_profile(&self) -> Option<&ColorProfileChunk> {
+        match self.chunk_data(self.color_profile?) {
+            ChunkData::ColorProfileChunk(chunk) => Some(chunk),
+            _ => None,
+        }
+    }
+
+    /// colors of the sprite, used to convert indexed cels to RGBA
+    pub fn color_palette(&self) -> Palette {
+        self.palette_at(0)
+    }
+
+    /// palette in effect at `frame_index`, with the changes of the
+    /// palette chunks of the previous frames applied
+    pub fn palette_at(&self, frame_index: usize) -> Palette {
+        Palette::at_frame(&self.ase.frames, frame_index)
+    }
+}
+
+impl From<Aseprite> for Sprite {
+    fn from(ase: Aseprite) -> Self {
+        Sprite::from_aseprite(ase)
+    }
+}
+
+impl From<Sprite> for Aseprite {
+    fn from(sprite: Sprite) -> Self {
+        sprite.into_aseprite()
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{LoopAnimationDirection, Sprite, Tag};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Playback {
+    /// the animation starts over after its last frame
+    Loop,
+    /// the animation stops on its last frame
+    OneShot,
+}
+
+/// frames played by a tag, in order, with their durations
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Timeline {
+    pub direction: LoopAnimationDirection,
+    /// (frame index, duration in milliseconds) of one loop
+    pub frames: Vec<(usize, u16)>,
+}
+
+impl Timeline {
+    fn played_frames(
+        &self,
+        playback: Playback,
+    ) -> impl Iterator<Item = (usize, u16)> + '_ {
+        let back_to_first = match playback {
+            Playback::OneShot
+                if self.direction == LoopAnimationDirection::PingPong
+                    && self.frames.len() > 1 =>
+            {
+                self.frames.first().copied()
+            }
+            _ => None,
+        };
+        self.frames.iter().copied().chain(back_to_first)
+    }
+
+    /// frames played once, ping-pong comes back to its first frame
+    pub fn one_shot_frames(&self) -> Vec<(usize, u16)> {
+        self.played_frames(Playback::OneShot).collect()
+    }
+
+    /// total duration in milliseconds
+    pub fn duration(&self, playback: Playback) -> u64 {
+        self.played_frames(playback)
+            .map(|(_, duration)| duration as u64)
+            .sum()
+    }
+
+    /// frame shown `time` milliseconds after the animation started,
+    /// a frame is shown from its start time until the next one starts
+    pub fn frame_at(&self, time: u64, playback: Playback) -> Option<usize> {
+        let duration = self.duration(playback);
+        let mut time = match playback {
+            Playback::Loop if duration > 0 => time % duration,
+            _ => time,
+        };
+
+        let mut last = None;
+        for (frame, frame_duration) in self.played_frames(playback) {
+            if time < frame_duration as u64 {
+                return Some(frame);
+            }
+            time -= frame_duration as u64;
+            last = Some(frame);
+        }
+        last
+    }
+}
+
+impl Sprite {
+    /// timeline of a tag, frames outside of the sprite are left out
+    pub fn tag_timeline(&self, tag: &Tag) -> Timeline {
+        let frames = tag
+            .frame_sequence()
+            .into_iter()
+            .filter_map(|i| {
+                let frame = self.aseprite().frames.get(i)?;
+                Some((i, frame.frame_duration_milliseconds))
+            })
+            .collect();
+        Timeline {
+            direction: tag.loop_animation_direction,
+            frames,
+        }
+    }
+
+    /// timeline of the tag named `tag_name`
+    pub fn timeline(&self, tag_name: &str) -> Option<Timeline> {
+        Some(self.tag_timeline(self.tag(tag_name)?))
+    }
+}
@@ -0,0 +1,17 @@
+/// how `Aseprite::write_with_options` stores image cels
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum CelCompression {
+    /// write cels as they are
+    #[default]
+    Keep,
+    /// compress raw cels with a zlib level from 0 (fastest) to 9 (smallest)
+    Compress(u32),
+    /// decompress compressed image cels into raw cels, for debugging
+    Decompress,
+}
+
+/// options of `Aseprite::write_with_options`
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct WriteOptions {
+    pub cel_compression: CelCompression,
+}
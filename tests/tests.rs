@@ -1,6 +1,5 @@
 use ase::*;
-use std::fs;
-use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::io::{Cursor, Read, Seek, SeekFrom};
 
 fn test_read(fname: &str) -> std::io::Result<()> {
     let mut file = std::fs::File::open(fname)?;
@@ -42,3 +41,2917 @@ fn rw() -> std::io::Result<()> {
     test_rw("sample_aseprite_files/sliced.aseprite")?;
     Ok(())
 }
+
+#[test]
+fn header_fields() -> std::io::Result<()> {
+    let mut bytes = std::fs::read("sample_aseprite_files/simple.aseprite")?;
+    bytes[20] = 1;
+    bytes[29] = 2;
+    bytes[36..38].copy_from_slice(&(-3i16).to_le_bytes());
+    bytes[38..40].copy_from_slice(&5i16.to_le_bytes());
+    bytes[40..42].copy_from_slice(&8u16.to_le_bytes());
+    bytes[42..44].copy_from_slice(&12u16.to_le_bytes());
+    bytes[127] = 3;
+
+    let ase = Aseprite::from_read(&mut Cursor::new(&bytes))?;
+    let header = &ase.header;
+    assert_eq!(
+        (
+            header.grid_x,
+            header.grid_y,
+            header.grid_width,
+            header.grid_height
+        ),
+        (-3, 5, 8, 12)
+    );
+    assert_eq!(header.reserved_after_speed[0], 1);
+    assert_eq!(header.reserved_after_transparent_palette_entry[0], 2);
+    assert_eq!(header.reserved[83], 3);
+
+    let mut wtr = Cursor::new(vec![]);
+    ase.write(&mut wtr)?;
+    assert_eq!(wtr.into_inner(), bytes);
+
+    let header = Header::new(1, 1);
+    assert_eq!((header.grid_width, header.grid_height), (16, 16));
+    Ok(())
+}
+
+fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn chunk_rw(chunk: &Chunk, header: &Header) -> std::io::Result<Chunk> {
+    let mut wtr = Cursor::new(vec![]);
+    chunk.write(&mut wtr)?;
+    let bytes = wtr.into_inner();
+
+    let mut read = Cursor::new(bytes.clone());
+    let read_chunk = Chunk::from_read(&mut read, header)?;
+    assert_eq!(read.position() as usize, bytes.len());
+
+    let mut wtr = Cursor::new(vec![]);
+    read_chunk.write(&mut wtr)?;
+    assert_eq!(wtr.into_inner(), bytes);
+
+    Ok(read_chunk)
+}
+
+#[test]
+fn tileset_chunk_rw() -> std::io::Result<()> {
+    let header = Header::default();
+    let tiles: Vec<u8> = (0..2 * 2 * 2 * 4).map(|i| i as u8).collect();
+    let chunk = Chunk::new(ChunkData::TilesetChunk(TilesetChunk {
+        tileset_id: 3,
+        flags: tileset_chunk::Flags::LinkToExternalFile
+            | tileset_chunk::Flags::TilesInsideFile
+            | tileset_chunk::Flags::EmptyTileIsZero,
+        number_of_tiles: 2,
+        tile_width: 2,
+        tile_height: 2,
+        base_index: 1,
+        name: "terrain".to_owned(),
+        external_tileset: Some(ExternalTileset {
+            external_file_id: 7,
+            tileset_id: 9,
+        }),
+        zlib_compressed_data: Some(zlib_compress(&tiles)),
+        user_data: None,
+        tiles_user_data: vec![],
+    }));
+
+    let chunk = chunk_rw(&chunk, &header)?;
+    let tileset = match chunk.chunk_data {
+        ChunkData::TilesetChunk(tileset) => tileset,
+        other => panic!("expected tileset chunk, got {:?}", other),
+    };
+    assert_eq!(tileset.name, "terrain");
+    assert_eq!(tileset.image_height(), 4);
+    assert_eq!(tileset.external_tileset.as_ref().unwrap().tileset_id, 9);
+    match tileset.pixels(&header.color_depth)? {
+        Some(Pixels::RGBA(pixels)) => {
+            assert_eq!(pixels.len(), 2 * 4);
+            assert_eq!(pixels[1].r, 4);
+        }
+        other => panic!("expected rgba pixels, got {:?}", other),
+    }
+
+    // flags of newer versions are kept
+    let mut bytes = vec![];
+    Chunk::new(ChunkData::TilesetChunk(tileset)).write(&mut bytes)?;
+    bytes[10..14].copy_from_slice(&0x107u32.to_le_bytes());
+    let chunk = Chunk::from_read(&mut Cursor::new(&bytes), &header)?;
+    match &chunk.chunk_data {
+        ChunkData::TilesetChunk(tileset) => {
+            assert_eq!(tileset.flags.bits(), 0x107)
+        }
+        other => panic!("expected tileset chunk, got {:?}", other),
+    }
+    let mut written = vec![];
+    chunk.write(&mut written)?;
+    assert_eq!(written, bytes);
+
+    Ok(())
+}
+
+#[test]
+fn tilemap_cel_rw() -> std::io::Result<()> {
+    let header = Header::default();
+    let mut tiles = vec![];
+    for value in &[1u32, 0x8000_0002, 0x2000_0000, 0x4000_0003] {
+        tiles.extend_from_slice(&value.to_le_bytes());
+    }
+    let chunk = Chunk::new(ChunkData::CelChunk(CelChunk {
+        layer_index: 1,
+        x_position: -4,
+        y_position: 8,
+        opacity_level: 255,
+        cel: Cel::CompressedTilemap {
+            width_in_tiles: 2,
+            height_in_tiles: 2,
+            bits_per_tile: 32,
+            bitmask_tile_id: 0x1fff_ffff,
+            bitmask_x_flip: 0x2000_0000,
+            bitmask_y_flip: 0x4000_0000,
+            bitmask_diagonal_flip: 0x8000_0000,
+            zlib_compressed_data: zlib_compress(&tiles),
+        },
+        user_data: None,
+    }));
+
+    let chunk = chunk_rw(&chunk, &header)?;
+    let cel = match chunk.chunk_data {
+        ChunkData::CelChunk(cel) => cel,
+        other => panic!("expected cel chunk, got {:?}", other),
+    };
+    let tilemap = cel.cel.tilemap()?.unwrap();
+    assert_eq!(tilemap.tiles.len(), 4);
+    assert_eq!(
+        tilemap.get(1, 0),
+        Some(&Tile {
+            tile_id: 2,
+            x_flip: false,
+            y_flip: false,
+            diagonal_flip: true,
+        })
+    );
+    assert!(tilemap.get(0, 1).unwrap().x_flip);
+    assert_eq!(tilemap.get(1, 1).unwrap().tile_id, 3);
+    assert!(tilemap.get(2, 0).is_none());
+
+    let mut layer = LayerChunk::new("tiles", true);
+    layer.layer_type = LayerType::Tilemap;
+    layer.tileset_index = Some(5);
+    let chunk = chunk_rw(&Chunk::new(ChunkData::LayerChunk(layer)), &header)?;
+    match chunk.chunk_data {
+        ChunkData::LayerChunk(layer) => {
+            assert_eq!(layer.tileset_index, Some(5))
+        }
+        other => panic!("expected layer chunk, got {:?}", other),
+    }
+
+    Ok(())
+}
+
+fn ase_rw(ase: &Aseprite) -> std::io::Result<Aseprite> {
+    let mut wtr = Cursor::new(vec![]);
+    ase.write(&mut wtr)?;
+    let bytes = wtr.into_inner();
+
+    let read_ase = Aseprite::from_read(&mut Cursor::new(bytes.clone()))?;
+    let mut wtr = Cursor::new(vec![]);
+    read_ase.write(&mut wtr)?;
+    assert_eq!(wtr.into_inner(), bytes);
+
+    Ok(read_ase)
+}
+
+#[test]
+fn external_files_chunk_rw() -> std::io::Result<()> {
+    let mut frame = Frame::new();
+    frame.add_chunk(Chunk::new(ChunkData::ExternalFilesChunk(
+        ExternalFilesChunk {
+            number_of_entries: 2,
+            entries: vec![
+                ExternalFile {
+                    entry_id: 1,
+                    file_type: ExternalFileType::Palette,
+                    file_name: "palettes/db32.aseprite".to_owned(),
+                },
+                ExternalFile {
+                    entry_id: 4,
+                    file_type: ExternalFileType::Tileset,
+                    file_name: "tilesets/terrain.aseprite".to_owned(),
+                },
+            ],
+        },
+    )));
+    frame.add_chunk(Chunk::new(ChunkData::TilesetChunk(TilesetChunk {
+        tileset_id: 0,
+        flags: tileset_chunk::Flags::LinkToExternalFile,
+        number_of_tiles: 1,
+        tile_width: 16,
+        tile_height: 16,
+        base_index: 1,
+        name: "terrain".to_owned(),
+        external_tileset: Some(ExternalTileset {
+            external_file_id: 4,
+            tileset_id: 2,
+        }),
+        zlib_compressed_data: None,
+        user_data: None,
+        tiles_user_data: vec![],
+    })));
+    let ase = ase_rw(&Aseprite::new(Header::new(16, 16), vec![frame]))?;
+
+    assert_eq!(
+        ase.external_file(1).map(|f| f.file_type),
+        Some(ExternalFileType::Palette)
+    );
+    assert!(ase.external_file(2).is_none());
+    let tileset = match &ase.frames[0].chunks[1].chunk_data {
+        ChunkData::TilesetChunk(tileset) => tileset,
+        other => panic!("expected tileset chunk, got {:?}", other),
+    };
+    let file = tileset.external_file(&ase).unwrap();
+    assert_eq!(file.file_name, "tilesets/terrain.aseprite");
+
+    // a count that disagrees with the entries isn't written
+    let chunk = Chunk::new(ChunkData::ExternalFilesChunk(ExternalFilesChunk {
+        number_of_entries: 3,
+        entries: vec![],
+    }));
+    assert!(matches!(
+        chunk.write(&mut vec![]),
+        Err(Error::InvalidData { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn user_data_properties_rw() -> std::io::Result<()> {
+    let header = Header::default();
+    let properties = vec![
+        Property {
+            name: "solid".to_owned(),
+            value: PropertyValue::Bool(true),
+        },
+        Property {
+            name: "hp".to_owned(),
+            value: PropertyValue::Int16(-12),
+        },
+        Property {
+            name: "speed".to_owned(),
+            value: PropertyValue::Fixed(Fixed::from_f32(1.5)),
+        },
+        Property {
+            name: "hitbox".to_owned(),
+            value: PropertyValue::Rect {
+                x: 1,
+                y: 2,
+                width: 3,
+                height: 4,
+            },
+        },
+        Property {
+            name: "path".to_owned(),
+            value: PropertyValue::Vector {
+                element_type: Some(PropertyType::Point),
+                elements: vec![
+                    PropertyValue::Point { x: 0, y: 0 },
+                    PropertyValue::Point { x: 5, y: -5 },
+                ],
+            },
+        },
+        Property {
+            name: "mixed".to_owned(),
+            value: PropertyValue::Vector {
+                element_type: None,
+                elements: vec![
+                    PropertyValue::String("a".to_owned()),
+                    PropertyValue::UInt64(u64::MAX),
+                    PropertyValue::Double(0.25),
+                ],
+            },
+        },
+        Property {
+            name: "nested".to_owned(),
+            value: PropertyValue::Map(vec![Property {
+                name: "id".to_owned(),
+                value: PropertyValue::Uuid([7; 16]),
+            }]),
+        },
+    ];
+    let chunk = Chunk::new(ChunkData::UserDataChunk(UserDataChunk {
+        flags: user_data_chunk::Flags::HasText
+            | user_data_chunk::Flags::HasProperties,
+        text: Some("enemy".to_owned()),
+        color: None,
+        properties_maps: vec![
+            PropertiesMap {
+                key: 0,
+                properties: properties.clone(),
+            },
+            PropertiesMap {
+                key: 3,
+                properties: vec![],
+            },
+        ],
+    }));
+
+    let chunk = chunk_rw(&chunk, &header)?;
+    let user_data = match chunk.chunk_data {
+        ChunkData::UserDataChunk(user_data) => user_data,
+        other => panic!("expected user data chunk, got {:?}", other),
+    };
+    assert_eq!(user_data.text.as_deref(), Some("enemy"));
+    let user_properties = user_data.properties(0).unwrap();
+    assert_eq!(user_properties.properties, properties);
+    assert_eq!(
+        user_properties.get("speed"),
+        Some(&PropertyValue::Fixed(Fixed::from_f32(1.5)))
+    );
+    assert!(user_data.properties(3).unwrap().properties.is_empty());
+
+    Ok(())
+}
+
+fn text_user_data(text: &str) -> Chunk {
+    Chunk::new(ChunkData::UserDataChunk(UserDataChunk {
+        flags: user_data_chunk::Flags::HasText,
+        text: Some(text.to_owned()),
+        ..Default::default()
+    }))
+}
+
+#[test]
+fn user_data_owners() -> std::io::Result<()> {
+    let tag = |name: &str| Tag {
+        from_tag: 0,
+        to_tag: 0,
+        loop_animation_direction: LoopAnimationDirection::Forward,
+        tag_color: RGB256::default(),
+        tag_name: name.to_owned(),
+        user_data: None,
+    };
+    let cel_extra = || {
+        Chunk::new(ChunkData::CelExtraChunk(CelExtraChunk {
+            flags: cel_extra_chunk::Flags::PreciseBounds,
+            precise_x_position: 0.5,
+            precise_y_position: 0.0,
+            width: 1.0,
+            height: 1.0,
+        }))
+    };
+
+    // the sample frame is palette, old palette, layer, slice, user data of
+    // the slice, cel, like Aseprite writes it
+    let mut file =
+        std::fs::File::open("sample_aseprite_files/sliced.aseprite")?;
+    let mut ase = Aseprite::from_read(&mut file)?;
+    let chunk_types = |ase: &Aseprite| {
+        ase.frames[0]
+            .chunks
+            .iter()
+            .map(|c| c.chunk_data.chunk_type())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(chunk_types(&ase), [0x2019, 0x0004, 0x2004, 0x2022, 0x2005]);
+
+    // add the user data Aseprite writes for the sprite, tags, layers and
+    // cels, in its chunk order
+    let chunks = std::mem::take(&mut ase.frames[0].chunks);
+    let mut chunks = chunks.into_iter();
+    let frame = &mut ase.frames[0];
+    frame
+        .add_chunk(chunks.next().unwrap())
+        .add_chunk(chunks.next().unwrap())
+        .add_chunk(text_user_data("sprite"))
+        .add_chunk(Chunk::new(ChunkData::FrameTagsChunk(FrameTagsChunk {
+            number_of_tags: 3,
+            tags: vec![tag("idle"), tag("walk"), tag("run")],
+        })))
+        .add_chunk(text_user_data("idle"))
+        .add_chunk(text_user_data("walk"))
+        .add_chunk(chunks.next().unwrap())
+        .add_chunk(text_user_data("layer"))
+        .add_chunk(chunks.next().unwrap())
+        .add_chunk(chunks.next().unwrap())
+        .add_chunk(cel_extra())
+        .add_chunk(text_user_data("cel"))
+        .add_chunk(text_user_data("orphan"));
+    let ase = ase_rw(&ase)?;
+
+    let text = |user_data: Option<&UserDataChunk>| {
+        user_data.and_then(|u| u.text.clone())
+    };
+    assert_eq!(text(ase.user_data()), Some("sprite".to_owned()));
+
+    // the user data stays after the chunks that don't own it
+    let chunks = &ase.frames[0].chunks;
+    assert_eq!(ase.frames[0].number_of_chunks, 8);
+    assert_eq!(chunks.iter().map(Chunk::chunk_count).sum::<usize>(), 14);
+    assert_eq!(
+        chunk_types(&ase),
+        [0x2019, 0x0004, 0x2018, 0x2004, 0x2022, 0x2005, 0x2006, 0x2020]
+    );
+    match &chunks[2].chunk_data {
+        ChunkData::FrameTagsChunk(tags) => {
+            assert_eq!(text(tags.tags[0].user_data()), Some("idle".to_owned()));
+            assert_eq!(text(tags.tags[1].user_data()), Some("walk".to_owned()));
+            assert!(tags.tags[2].user_data().is_none());
+        }
+        other => panic!("expected tags chunk, got {:?}", other),
+    }
+    match &chunks[3].chunk_data {
+        ChunkData::LayerChunk(layer) => {
+            assert_eq!(text(layer.user_data()), Some("layer".to_owned()))
+        }
+        other => panic!("expected layer chunk, got {:?}", other),
+    }
+    match &chunks[4].chunk_data {
+        ChunkData::SliceChunk(slice) => assert!(slice.user_data().is_some()),
+        other => panic!("expected slice chunk, got {:?}", other),
+    }
+    match (&chunks[5].chunk_data, &chunks[7].chunk_data) {
+        (ChunkData::CelChunk(cel), ChunkData::UserDataChunk(orphan)) => {
+            assert_eq!(text(cel.user_data()), Some("cel".to_owned()));
+            assert_eq!(orphan.text.as_deref(), Some("orphan"));
+        }
+        other => panic!("expected cel and user data chunks, got {:?}", other),
+    }
+
+    // user data read before a Cel Extra chunk keeps its place
+    let mut frame = Frame::new();
+    frame
+        .add_chunk(Chunk::new(ChunkData::LayerChunk(LayerChunk::new(
+            "layer", true,
+        ))))
+        .add_chunk(Chunk::new(ChunkData::CelChunk(solid_cel(
+            0,
+            0,
+            0,
+            1,
+            1,
+            rgba(1, 2, 3, 255),
+        ))))
+        .add_chunk(text_user_data("cel"))
+        .add_chunk(cel_extra())
+        .add_chunk(text_user_data("orphan"));
+    let ase = ase_rw(&Aseprite::new(Header::new(1, 1), vec![frame]))?;
+    assert_eq!(chunk_types(&ase), [0x2004, 0x2005, 0x2020, 0x2006, 0x2020]);
+
+    Ok(())
+}
+
+fn read_sprite(fname: &str) -> std::io::Result<Sprite> {
+    let mut file = std::fs::File::open(fname)?;
+    Ok(Sprite::from_aseprite(Aseprite::from_read(&mut file)?))
+}
+
+#[test]
+fn sprite() -> std::io::Result<()> {
+    let sprite = read_sprite("sample_aseprite_files/layered.aseprite")?;
+    let names: Vec<_> = sprite
+        .layers()
+        .map(|layer| (layer.index, layer.chunk.layer_name.clone()))
+        .collect();
+    assert_eq!(
+        names,
+        vec![(0, "Layer 1".to_owned()), (1, "Layer 2".to_owned())]
+    );
+    assert_eq!(sprite.layer_by_name("Layer 2").unwrap().index, 1);
+    assert_eq!(sprite.cel(1, 0).unwrap().y_position, 6);
+    assert!(sprite.cel(2, 0).is_none());
+    assert_eq!(sprite.palette().unwrap().palette_entries.len(), 32);
+
+    let sprite = read_sprite("sample_aseprite_files/animated.aseprite")?;
+    assert_eq!(sprite.frame_count(), 4);
+    assert!(sprite.frames().all(|f| f.duration_milliseconds == 100));
+    assert_eq!(sprite.cel(0, 1).unwrap().x_position, 0);
+    assert_eq!(sprite.cel(0, 2).unwrap().x_position, 2);
+
+    let sprite = read_sprite("sample_aseprite_files/sliced.aseprite")?;
+    let slice = sprite.slice("Slice").unwrap();
+    assert!(slice.keys[0].nine_patches_info.is_some());
+    assert_eq!(sprite.slices().count(), 1);
+    assert_eq!(sprite.tags().count(), 0);
+
+    let mut file_buf = vec![];
+    std::fs::File::open("sample_aseprite_files/sliced.aseprite")?
+        .read_to_end(&mut file_buf)?;
+    let mut wtr = Cursor::new(vec![]);
+    Aseprite::from(sprite).write(&mut wtr)?;
+    assert_eq!(wtr.into_inner(), file_buf);
+
+    Ok(())
+}
+
+#[test]
+fn layer_tree() {
+    let layer = |name: &str, level: u16, group: bool| {
+        let mut layer = LayerChunk::new(name, true);
+        layer.layer_child_level = level;
+        if group {
+            layer.layer_type = LayerType::Group;
+        }
+        layer
+    };
+
+    let mut frame = Frame::new();
+    let mut characters = layer("Characters", 0, true);
+    characters.opacity = 128;
+    let mut head = layer("Head", 2, false);
+    head.flags.remove(layer_chunk::Flags::Visible);
+    let mut hidden = layer("Hidden", 0, true);
+    hidden.flags.remove(layer_chunk::Flags::Visible);
+    for layer in vec![
+        layer("Background", 0, false),
+        characters,
+        layer("Hero", 1, true),
+        layer("Body", 2, false),
+        head,
+        layer("Shadow", 1, false),
+        hidden,
+        layer("Ghost", 1, false),
+        layer("Foreground", 0, false),
+    ] {
+        frame.add_chunk(Chunk::new(ChunkData::LayerChunk(layer)));
+    }
+    let mut header = Header::new(1, 1);
+    header.flags |= header::Flags::GroupHasOpacity;
+    let sprite = Sprite::from_aseprite(Aseprite::new(header, vec![frame]));
+
+    let tree = sprite.layer_tree();
+    assert_eq!(tree.roots(), &[0, 1, 6, 8]);
+    assert_eq!(tree.children(1), &[2, 5]);
+    assert_eq!(tree.children(2), &[3, 4]);
+    assert_eq!(tree.parent(3), Some(2));
+    assert_eq!(tree.parent(7), Some(6));
+    assert_eq!(tree.parent(8), None);
+
+    assert_eq!(tree.find("Characters/Hero/Body"), Some(3));
+    assert_eq!(tree.find("Characters/Shadow"), Some(5));
+    assert_eq!(tree.find("Body"), None);
+    assert_eq!(
+        sprite
+            .layer_by_path("Characters/Hero/Head")
+            .map(|l| l.chunk.layer_name.clone()),
+        Some("Head".to_owned())
+    );
+
+    assert!(tree.is_visible(3));
+    assert!(!tree.is_visible(4));
+    assert!(!tree.is_visible(7));
+    assert!(tree.is_visible(8));
+    assert_eq!(tree.opacity(0), 255);
+    assert_eq!(tree.opacity(3), 128);
+
+    let mut header = Header::new(1, 1);
+    header.flags = header::Flags::HasOpacity;
+    let mut layers = [layer("Group", 0, true), layer("Child", 1, false)];
+    layers[0].opacity = 10;
+    let layers: Vec<_> = layers.iter().collect();
+    let tree = LayerTree::new(&layers, &header);
+    assert_eq!(tree.opacity(1), 255);
+}
+
+#[test]
+fn linked_cels() {
+    let linked = |frame_position: u16| {
+        let mut cel = CelChunk::new(0, 0, 0, 0, 0, Pixels::Indexed(vec![]));
+        cel.cel = Cel::LinkedCel { frame_position };
+        cel
+    };
+
+    let mut source = CelChunk::new(0, 3, 4, 1, 1, Pixels::Indexed(vec![9]));
+    source.opacity_level = 100;
+    let cels = vec![
+        source,
+        linked(0),
+        linked(1),
+        linked(5),
+        linked(4),
+        linked(6),
+        linked(5),
+    ];
+    let frames = cels
+        .into_iter()
+        .map(|cel| {
+            let mut frame = Frame::new();
+            frame.add_chunk(Chunk::new(ChunkData::CelChunk(cel)));
+            frame
+        })
+        .collect();
+    let mut header = Header::new(1, 1);
+    header.color_depth = ColorDepth::Indexed;
+    let sprite = Sprite::from_aseprite(Aseprite::new(header, frames));
+
+    assert!(sprite
+        .cel(0, 1)
+        .unwrap()
+        .cel
+        .pixels(&ColorDepth::Indexed)
+        .unwrap()
+        .is_none());
+    for frame in 0..3 {
+        let cel = sprite.resolve_cel(0, frame).unwrap().unwrap();
+        assert_eq!((cel.x_position, cel.y_position), (3, 4));
+        assert_eq!(cel.opacity_level, 100);
+        match cel.cel.pixels(&ColorDepth::Indexed).unwrap() {
+            Some(Pixels::Indexed(indices)) => assert_eq!(indices, vec![9]),
+            other => panic!("expected indexed pixels, got {:?}", other),
+        }
+    }
+    assert!(sprite.resolve_cel(1, 0).unwrap().is_none());
+    assert!(sprite.resolve_cel(0, 3).is_err());
+    assert!(sprite.resolve_cel(0, 4).is_err());
+    assert!(sprite.resolve_cel(0, 5).is_err());
+
+    // every frame of a long chain resolves in a number of steps bounded by
+    // the frame count, and so does every frame leading into a cycle
+    let count = 1000;
+    let frames = (0..count)
+        .map(|i| {
+            let cel = match i {
+                0 => CelChunk::new(0, 0, 0, 1, 1, Pixels::Indexed(vec![1])),
+                1 => linked(count as u16 - 1),
+                i => linked(i as u16 - 1),
+            };
+            let mut frame = Frame::new();
+            frame.add_chunk(Chunk::new(ChunkData::CelChunk(cel)));
+            frame
+        })
+        .collect();
+    let mut header = Header::new(1, 1);
+    header.color_depth = ColorDepth::Indexed;
+    let sprite = Sprite::from_aseprite(Aseprite::new(header, frames));
+    assert!(sprite.resolve_cel(0, 0).unwrap().is_some());
+    for frame in 1..count {
+        assert!(sprite.resolve_cel(0, frame).is_err());
+    }
+}
+
+#[test]
+fn cel_image() -> std::io::Result<()> {
+    let sprite = read_sprite("sample_aseprite_files/simple.aseprite")?;
+    let color_depth = sprite.header().color_depth;
+    let cel = sprite.cel(0, 0).unwrap();
+    let image = cel.image(&color_depth)?.unwrap();
+    assert_eq!(image.width, cel.cel.w().unwrap() as u32);
+    assert_eq!(image.height, cel.cel.h().unwrap() as u32);
+    assert_eq!(image.row(0).unwrap().len(), image.width as usize * 4);
+    assert_eq!(image.rows().count(), image.height as usize);
+    assert!(image.row(image.height).is_none());
+    assert!(image.get(image.width, 0).is_none());
+    match cel.cel.pixels(&color_depth)? {
+        Some(Pixels::RGBA(pixels)) => {
+            let x = 3;
+            let y = 2;
+            let expected = pixels[(y * image.width + x) as usize];
+            assert_eq!(image.get(x, y), Some(Pixel::RGBA(expected)));
+        }
+        other => panic!("expected rgba pixels, got {:?}", other),
+    }
+
+    let cel = CelChunk::new(
+        0,
+        0,
+        0,
+        2,
+        1,
+        Pixels::Grayscale(vec![
+            Grayscale256 { v: 10, a: 255 },
+            Grayscale256 { v: 20, a: 128 },
+        ]),
+    );
+    let mut image = cel.image(&ColorDepth::Grayscale)?.unwrap();
+    assert_eq!(image.data, vec![10, 255, 20, 128]);
+    image.set(0, 0, Pixel::Grayscale(Grayscale256 { v: 1, a: 2 }));
+    assert_eq!(image.row(0), Some(&[1, 2, 20, 128][..]));
+    assert_eq!(
+        image.get(1, 0),
+        Some(Pixel::Grayscale(Grayscale256 { v: 20, a: 128 }))
+    );
+
+    // images are bounded by the limits
+    let image = Image::new(3, 2, ColorDepth::Grayscale)?;
+    assert_eq!(image.data, vec![0; 12]);
+    assert!(matches!(
+        Image::new(u32::MAX, u32::MAX, ColorDepth::RGBA),
+        Err(Error::InvalidData { .. })
+    ));
+    let limits = Limits {
+        max_image_size: 11,
+        ..Limits::default()
+    };
+    assert!(
+        Image::new_with_limits(3, 2, ColorDepth::Grayscale, &limits).is_err()
+    );
+
+    // an image whose data doesn't match its size doesn't panic
+    let mut image = Image::new(2, 2, ColorDepth::RGBA)?;
+    image.data.truncate(10);
+    assert!(image.get(1, 0).is_some());
+    assert!(image.get(1, 1).is_none());
+    assert!(image.row(1).is_none());
+    assert!(image.row_mut(1).is_none());
+    assert_eq!(image.rows().count(), 1);
+    image.set(1, 1, Pixel::RGBA(rgba(1, 2, 3, 4)));
+    assert_eq!(image.data.len(), 10);
+
+    Ok(())
+}
+
+fn rgba(r: u8, g: u8, b: u8, a: u8) -> RGBA256 {
+    RGBA256 { r, g, b, a }
+}
+
+fn solid_cel(
+    layer_index: u16,
+    x: i16,
+    y: i16,
+    w: u16,
+    h: u16,
+    color: RGBA256,
+) -> CelChunk {
+    let pixels = Pixels::RGBA(vec![color; w as usize * h as usize]);
+    CelChunk::new(layer_index, x, y, w, h, pixels)
+}
+
+#[test]
+fn render_frame() -> std::io::Result<()> {
+    let sprite = read_sprite("sample_aseprite_files/layered.aseprite")?;
+    let canvas = sprite.render_frame(0)?;
+    assert_eq!((canvas.width, canvas.height), (32, 32));
+    let top = sprite.cel(1, 0).unwrap();
+    let top_image = top.image(&ColorDepth::RGBA)?.unwrap();
+    for y in 0..top_image.height {
+        for x in 0..top_image.width {
+            if let Some(Pixel::RGBA(c)) = top_image.get(x, y) {
+                if c.a == 255 {
+                    let cx = x + top.x_position as u32;
+                    let cy = y + top.y_position as u32;
+                    assert_eq!(canvas.get(cx, cy), Some(Pixel::RGBA(c)));
+                }
+            }
+        }
+    }
+    assert_eq!(canvas.get(0, 0), Some(Pixel::RGBA(rgba(0, 0, 0, 0))));
+
+    let red = rgba(255, 0, 0, 255);
+    let blue = rgba(0, 0, 255, 255);
+    let mut group = LayerChunk::new("group", false);
+    group.layer_type = LayerType::Group;
+    let mut child = LayerChunk::new("child", true);
+    child.layer_child_level = 1;
+    let mut reference = LayerChunk::new("reference", true);
+    reference.flags |= layer_chunk::Flags::ReferenceLayer;
+    let mut top = LayerChunk::new("top", true);
+    top.opacity = 128;
+
+    let mut frame = Frame::new();
+    for layer in [
+        LayerChunk::new("bottom", true),
+        top,
+        group,
+        child,
+        reference,
+    ] {
+        frame.add_chunk(Chunk::new(ChunkData::LayerChunk(layer)));
+    }
+    frame.add_chunk(Chunk::new(ChunkData::CelChunk(solid_cel(
+        0, 0, 0, 2, 2, red,
+    ))));
+    frame.add_chunk(Chunk::new(ChunkData::CelChunk(solid_cel(
+        1, 1, 1, 2, 2, blue,
+    ))));
+    frame.add_chunk(Chunk::new(ChunkData::CelChunk(solid_cel(
+        3, 0, 0, 3, 3, blue,
+    ))));
+    frame.add_chunk(Chunk::new(ChunkData::CelChunk(solid_cel(
+        4, 0, 0, 3, 3, blue,
+    ))));
+    let mut header = Header::new(3, 3);
+    let sprite =
+        Sprite::from_aseprite(Aseprite::new(Header::new(3, 3), vec![frame]));
+
+    let canvas = sprite.render_frame(0)?;
+    assert_eq!(canvas.get(0, 0), Some(Pixel::RGBA(red)));
+    assert_eq!(canvas.get(1, 1), Some(Pixel::RGBA(rgba(127, 0, 128, 255))));
+    assert_eq!(canvas.get(2, 2), Some(Pixel::RGBA(rgba(0, 0, 255, 128))));
+    assert_eq!(canvas.get(2, 0), Some(Pixel::RGBA(rgba(0, 0, 0, 0))));
+
+    header.flags = header::Flags::empty();
+    let sprite = Sprite::from_aseprite(Aseprite::new(
+        header,
+        sprite.into_aseprite().frames,
+    ));
+    let canvas = sprite.render_frame(0)?;
+    assert_eq!(canvas.get(1, 1), Some(Pixel::RGBA(blue)));
+
+    Ok(())
+}
+
+#[test]
+fn render_tilemap() -> std::io::Result<()> {
+    let (a, b, c, d) = (
+        rgba(255, 0, 0, 255),
+        rgba(0, 255, 0, 255),
+        rgba(0, 0, 255, 255),
+        rgba(255, 255, 255, 255),
+    );
+    let mut tiles = vec![0; 2 * 2 * 4];
+    for color in &[a, b, c, d] {
+        tiles.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+    }
+    let tileset = TilesetChunk {
+        tileset_id: 2,
+        flags: tileset_chunk::Flags::TilesInsideFile
+            | tileset_chunk::Flags::EmptyTileIsZero,
+        number_of_tiles: 2,
+        tile_width: 2,
+        tile_height: 2,
+        base_index: 1,
+        name: "tiles".to_owned(),
+        external_tileset: None,
+        zlib_compressed_data: Some(zlib_compress(&tiles)),
+        user_data: None,
+        tiles_user_data: vec![],
+    };
+    // a tile, flipped along x, along the diagonal, and one not in the tileset
+    let mut tilemap = vec![];
+    for value in &[1u32, 0x2000_0001, 0x8000_0001, 7] {
+        tilemap.extend_from_slice(&value.to_le_bytes());
+    }
+    let cel = CelChunk {
+        layer_index: 0,
+        x_position: 0,
+        y_position: 0,
+        opacity_level: 255,
+        cel: Cel::CompressedTilemap {
+            width_in_tiles: 4,
+            height_in_tiles: 1,
+            bits_per_tile: 32,
+            bitmask_tile_id: 0x1fff_ffff,
+            bitmask_x_flip: 0x2000_0000,
+            bitmask_y_flip: 0x4000_0000,
+            bitmask_diagonal_flip: 0x8000_0000,
+            zlib_compressed_data: zlib_compress(&tilemap),
+        },
+        user_data: None,
+    };
+    let mut layer = LayerChunk::new("tilemap", true);
+    layer.layer_type = LayerType::Tilemap;
+    layer.tileset_index = Some(2);
+
+    let mut frame = Frame::new();
+    frame
+        .add_chunk(Chunk::new(ChunkData::TilesetChunk(tileset)))
+        .add_chunk(Chunk::new(ChunkData::LayerChunk(layer)))
+        .add_chunk(Chunk::new(ChunkData::CelChunk(cel)));
+    let ase = ase_rw(&Aseprite::new(Header::new(8, 2), vec![frame]))?;
+    let mut sprite = Sprite::from_aseprite(ase);
+    let canvas = sprite.render_frame(0)?;
+    let rows: Vec<Vec<_>> = (0..2)
+        .map(|y| (0..8).map(|x| canvas.get(x, y)).collect())
+        .collect();
+    let none = rgba(0, 0, 0, 0);
+    for (row, expected) in rows.iter().zip(&[
+        [a, b, b, a, a, c, none, none],
+        [c, d, d, c, b, d, none, none],
+    ]) {
+        let expected: Vec<_> =
+            expected.iter().map(|&c| Some(Pixel::RGBA(c))).collect();
+        assert_eq!(row, &expected);
+    }
+
+    // a tilemap without its tileset can't be rendered
+    let mut ase = sprite.into_aseprite();
+    ase.frames[0].chunks.remove(0);
+    sprite = Sprite::from_aseprite(ase);
+    match sprite.render_frame(0) {
+        Err(Error::InvalidData { message, .. }) => {
+            assert!(message.contains("Tileset 2"), "{}", message)
+        }
+        other => panic!("expected missing tileset error, got {:?}", other),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn blend_modes() -> std::io::Result<()> {
+    use ase::blend::{blend, blend_legacy};
+
+    let backdrop = rgba(200, 100, 50, 255);
+    let src = rgba(100, 200, 250, 255);
+    let expected = [
+        (BlendMode::Normal, rgba(100, 200, 250, 255)),
+        (BlendMode::Multiply, rgba(78, 78, 49, 255)),
+        (BlendMode::Screen, rgba(222, 222, 251, 255)),
+        (BlendMode::Overlay, rgba(188, 157, 98, 255)),
+        (BlendMode::Darken, rgba(100, 100, 50, 255)),
+        (BlendMode::Lighten, rgba(200, 200, 250, 255)),
+        (BlendMode::ColorDodge, rgba(255, 255, 255, 255)),
+        (BlendMode::ColorBurn, rgba(115, 57, 46, 255)),
+        (BlendMode::HardLight, rgba(157, 188, 247, 255)),
+        (BlendMode::SoftLight, rgba(191, 134, 111, 255)),
+        (BlendMode::Difference, rgba(100, 100, 200, 255)),
+        (BlendMode::Exclusion, rgba(144, 144, 202, 255)),
+        (BlendMode::Addition, rgba(255, 255, 255, 255)),
+        (BlendMode::Subtract, rgba(100, 0, 0, 255)),
+        (BlendMode::Divide, rgba(255, 128, 51, 255)),
+    ];
+    for (mode, color) in expected {
+        assert_eq!(blend(mode, backdrop, src, 255), color, "{:?}", mode);
+    }
+
+    let red = rgba(255, 0, 0, 255);
+    let green = rgba(0, 255, 0, 255);
+    let gray = rgba(128, 128, 128, 255);
+    assert_eq!(blend(BlendMode::Hue, red, green, 255), rgba(0, 129, 0, 255));
+    assert_eq!(blend(BlendMode::Saturation, green, red, 255), green);
+    assert_eq!(
+        blend(BlendMode::Color, gray, red, 255),
+        rgba(255, 73, 73, 255)
+    );
+    assert_eq!(
+        blend(BlendMode::Luminosity, red, gray, 255),
+        rgba(255, 73, 73, 255)
+    );
+
+    // the blended color is composited with the source alpha and opacity
+    let half = rgba(100, 200, 250, 128);
+    assert_eq!(
+        blend(BlendMode::Multiply, backdrop, half, 255),
+        rgba(139, 89, 50, 255)
+    );
+    assert_eq!(
+        blend(BlendMode::Multiply, rgba(0, 0, 0, 0), src, 128),
+        rgba(100, 200, 250, 128)
+    );
+
+    // over a semi-transparent backdrop Aseprite's new blending goes halfway
+    // to the normal blend, the legacy one blends with the backdrop color
+    let translucent = rgba(200, 100, 50, 128);
+    assert_eq!(
+        blend(BlendMode::Multiply, translucent, src, 255),
+        rgba(83, 108, 99, 255)
+    );
+    assert_eq!(
+        blend(BlendMode::Multiply, translucent, half, 255),
+        rgba(124, 116, 99, 192)
+    );
+    assert_eq!(
+        blend_legacy(BlendMode::Multiply, translucent, src, 255),
+        rgba(78, 78, 49, 255)
+    );
+    for (mode, _) in expected {
+        assert_eq!(
+            blend(mode, backdrop, half, 128),
+            blend_legacy(mode, backdrop, half, 128),
+            "{:?}",
+            mode
+        );
+    }
+
+    let mut top = LayerChunk::new("top", true);
+    top.blend_mode = BlendMode::Multiply;
+    let mut frame = Frame::new();
+    frame.add_chunk(Chunk::new(ChunkData::LayerChunk(LayerChunk::new(
+        "bottom", true,
+    ))));
+    frame.add_chunk(Chunk::new(ChunkData::LayerChunk(top)));
+    frame.add_chunk(Chunk::new(ChunkData::CelChunk(solid_cel(
+        0, 0, 0, 2, 1, backdrop,
+    ))));
+    frame.add_chunk(Chunk::new(ChunkData::CelChunk(solid_cel(
+        1, 1, 0, 1, 1, src,
+    ))));
+    let sprite =
+        Sprite::from_aseprite(Aseprite::new(Header::new(2, 1), vec![frame]));
+    let canvas = sprite.render_frame(0)?;
+    assert_eq!(canvas.get(0, 0), Some(Pixel::RGBA(backdrop)));
+    assert_eq!(canvas.get(1, 0), Some(Pixel::RGBA(rgba(78, 78, 49, 255))));
+
+    Ok(())
+}
+
+fn palette_entry(color: RGBA256, name: Option<&str>) -> PaletteEntry {
+    PaletteEntry {
+        flags: match name {
+            Some(_) => palette_chunk::Flags::HasName,
+            None => palette_chunk::Flags::empty(),
+        },
+        color,
+        color_name: name.map(str::to_owned),
+    }
+}
+
+#[test]
+fn indexed_to_rgba() -> std::io::Result<()> {
+    let red = rgba(255, 0, 0, 255);
+    let green = rgba(0, 255, 0, 255);
+    let clear = rgba(0, 0, 0, 0);
+
+    let mut old4 = Frame::new();
+    old4.add_chunk(Chunk::new(ChunkData::OldPaletteChunk4(OldPaletteChunk4 {
+        number_of_packets: 2,
+        packets: vec![
+            old_palette_chunk4::Packet {
+                palette_entries_to_skip: 0,
+                colors: vec![RGB256 { r: 1, g: 2, b: 3 }],
+            },
+            old_palette_chunk4::Packet {
+                palette_entries_to_skip: 1,
+                colors: vec![RGB256 { r: 4, g: 5, b: 6 }],
+            },
+        ],
+    })));
+    let palette = Palette::from_frame(&old4);
+    assert_eq!(palette.len(), 3);
+    assert_eq!(palette.color(0), Some(rgba(1, 2, 3, 255)));
+    assert_eq!(palette.color(2), Some(rgba(4, 5, 6, 255)));
+
+    let mut old11 = Frame::new();
+    old11.add_chunk(Chunk::new(ChunkData::OldPaletteChunk11(
+        OldPaletteChunk11 {
+            number_of_packets: 1,
+            packets: vec![old_palette_chunk11::Packet {
+                palette_entries_to_skip: 0,
+                colors: vec![RGB64 { r: 63, g: 32, b: 0 }],
+            }],
+        },
+    )));
+    assert_eq!(
+        Palette::from_frame(&old11).color(0),
+        Some(rgba(255, 130, 0, 255))
+    );
+
+    // packets of 256 colors store a count of 0
+    let old11 = Chunk::new(ChunkData::OldPaletteChunk11(OldPaletteChunk11 {
+        number_of_packets: 2,
+        packets: vec![
+            old_palette_chunk11::Packet {
+                palette_entries_to_skip: 0,
+                colors: vec![RGB64 { r: 1, g: 2, b: 3 }; 256],
+            },
+            old_palette_chunk11::Packet {
+                palette_entries_to_skip: 0,
+                colors: vec![RGB64 { r: 4, g: 5, b: 6 }],
+            },
+        ],
+    }));
+    match chunk_rw(&old11, &Header::default())?.chunk_data {
+        ChunkData::OldPaletteChunk11(chunk) => {
+            assert_eq!(chunk.packets[0].colors.len(), 256);
+            assert_eq!(chunk.packets[1].colors, [RGB64 { r: 4, g: 5, b: 6 }]);
+        }
+        other => panic!("expected old palette chunk, got {:?}", other),
+    }
+    let empty = Chunk::new(ChunkData::OldPaletteChunk4(OldPaletteChunk4 {
+        number_of_packets: 1,
+        packets: vec![old_palette_chunk4::Packet {
+            palette_entries_to_skip: 0,
+            colors: vec![],
+        }],
+    }));
+    assert!(matches!(
+        empty.write(&mut vec![]),
+        Err(Error::InvalidData { .. })
+    ));
+
+    let pixels = Pixels::Grayscale(vec![Grayscale256 { v: 10, a: 20 }]);
+    assert_eq!(
+        pixels.to_rgba(&Palette::new(), None),
+        vec![rgba(10, 10, 10, 20)]
+    );
+
+    let mut header = Header::new(2, 1);
+    header.color_depth = ColorDepth::Indexed;
+    let mut background = LayerChunk::new("background", true);
+    background.flags |= layer_chunk::Flags::Backgrount;
+    let mut frame = Frame::new();
+    frame.add_chunk(Chunk::new(ChunkData::PaletteChunk(PaletteChunk {
+        new_palette_size: 2,
+        first_color_index_to_change: 0,
+        last_color_index_to_change: 1,
+        palette_entries: vec![
+            palette_entry(red, None),
+            palette_entry(green, Some("green")),
+        ],
+        user_data: None,
+    })));
+    frame.add_chunk(Chunk::new(ChunkData::LayerChunk(background)));
+    frame.add_chunk(Chunk::new(ChunkData::LayerChunk(LayerChunk::new(
+        "top", true,
+    ))));
+    frame.add_chunk(Chunk::new(ChunkData::CelChunk(CelChunk::new(
+        0,
+        0,
+        0,
+        2,
+        1,
+        Pixels::Indexed(vec![0, 0]),
+    ))));
+    frame.add_chunk(Chunk::new(ChunkData::CelChunk(CelChunk::new(
+        1,
+        0,
+        0,
+        2,
+        1,
+        Pixels::Indexed(vec![0, 1]),
+    ))));
+    let sprite = Sprite::from_aseprite(Aseprite::new(header, vec![frame]));
+
+    let palette = sprite.color_palette();
+    assert_eq!(palette.color_name(1), Some("green"));
+    let cel = sprite
+        .cel(1, 0)
+        .unwrap()
+        .image(&ColorDepth::Indexed)?
+        .unwrap();
+    let image = cel.to_rgba(&palette, Some(0));
+    assert_eq!(image.get(0, 0), Some(Pixel::RGBA(clear)));
+    assert_eq!(image.get(1, 0), Some(Pixel::RGBA(green)));
+    let image = cel.to_rgba(&palette, None);
+    assert_eq!(image.get(0, 0), Some(Pixel::RGBA(red)));
+
+    // index 0 is only transparent outside of the background layer
+    let canvas = sprite.render_frame(0)?;
+    assert_eq!(canvas.get(0, 0), Some(Pixel::RGBA(red)));
+    assert_eq!(canvas.get(1, 0), Some(Pixel::RGBA(green)));
+
+    Ok(())
+}
+
+#[test]
+fn palette_deltas() -> std::io::Result<()> {
+    let gray = |v: u8| rgba(v, v, v, 255);
+    let palette_chunk = |size: u32, first: u32, entries: Vec<PaletteEntry>| {
+        Chunk::new(ChunkData::PaletteChunk(PaletteChunk {
+            new_palette_size: size,
+            first_color_index_to_change: first,
+            last_color_index_to_change: first + entries.len() as u32 - 1,
+            palette_entries: entries,
+            user_data: None,
+        }))
+    };
+
+    let mut frames = vec![Frame::new(), Frame::new(), Frame::new()];
+    frames[0].add_chunk(palette_chunk(
+        3,
+        0,
+        vec![
+            palette_entry(gray(0), Some("black")),
+            palette_entry(gray(1), Some("dark")),
+            palette_entry(gray(2), None),
+        ],
+    ));
+    frames[1].add_chunk(palette_chunk(
+        3,
+        1,
+        vec![palette_entry(gray(10), None)],
+    ));
+    frames[2].add_chunk(palette_chunk(
+        5,
+        4,
+        vec![palette_entry(gray(40), Some("new"))],
+    ));
+    let ase = ase_rw(&Aseprite::new(Header::new(1, 1), frames))?;
+    match &ase.frames[1].chunks[0].chunk_data {
+        ChunkData::PaletteChunk(chunk) => {
+            assert_eq!(chunk.palette_entries.len(), 1)
+        }
+        other => panic!("expected palette chunk, got {:?}", other),
+    }
+    let sprite = Sprite::from_aseprite(ase);
+
+    let colors = |palette: &Palette| {
+        palette.colors.iter().map(|c| c.color).collect::<Vec<_>>()
+    };
+    let palette = sprite.palette_at(0);
+    assert_eq!(colors(&palette), vec![gray(0), gray(1), gray(2)]);
+    assert_eq!(palette.color_name(1), Some("dark"));
+
+    let palette = sprite.palette_at(1);
+    assert_eq!(colors(&palette), vec![gray(0), gray(10), gray(2)]);
+    assert_eq!(palette.color_name(0), Some("black"));
+    assert_eq!(palette.color_name(1), None);
+
+    let palette = sprite.palette_at(2);
+    assert_eq!(
+        colors(&palette),
+        vec![gray(0), gray(10), gray(2), rgba(0, 0, 0, 0), gray(40)]
+    );
+    assert_eq!(palette.color_name(4), Some("new"));
+    assert_eq!(sprite.palette_at(9), palette);
+
+    Ok(())
+}
+
+/// split a PNG file into its chunks, checking their CRCs
+fn png_chunks(png: &[u8]) -> Vec<(String, Vec<u8>)> {
+    use byteorder::{BigEndian, ReadBytesExt};
+
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    let mut rdr = Cursor::new(&png[8..]);
+    let mut chunks = vec![];
+    while (rdr.position() as usize) < png.len() - 8 {
+        let len = rdr.read_u32::<BigEndian>().unwrap() as usize;
+        let mut body = vec![0; 4 + len];
+        rdr.read_exact(&mut body).unwrap();
+        let mut crc = flate2::Crc::new();
+        crc.update(&body);
+        assert_eq!(rdr.read_u32::<BigEndian>().unwrap(), crc.sum());
+        let chunk_type = String::from_utf8(body[..4].to_vec()).unwrap();
+        chunks.push((chunk_type, body[4..].to_vec()));
+    }
+    chunks
+}
+
+/// decode an 8-bit RGB or RGBA PNG, like the ones Aseprite exports
+fn decode_png(png: &[u8]) -> Image {
+    let chunks = png_chunks(png);
+    let ihdr = &chunks.iter().find(|(t, _)| t == "IHDR").unwrap().1;
+    let width = u32::from_be_bytes([ihdr[0], ihdr[1], ihdr[2], ihdr[3]]);
+    let height = u32::from_be_bytes([ihdr[4], ihdr[5], ihdr[6], ihdr[7]]);
+    let channels = match (ihdr[8], ihdr[9], ihdr[12]) {
+        (8, 2, 0) => 3,
+        (8, 6, 0) => 4,
+        other => {
+            panic!("unsupported PNG (depth, color, interlace) {:?}", other)
+        }
+    };
+    let idat: Vec<u8> = chunks
+        .iter()
+        .filter(|(t, _)| t == "IDAT")
+        .flat_map(|(_, data)| data.iter().copied())
+        .collect();
+    let filtered = helpers::zlib_decompress(&idat).unwrap();
+
+    let stride = width as usize * channels;
+    let mut prev = vec![0u8; stride];
+    let mut data = Vec::with_capacity(width as usize * height as usize * 4);
+    for line in filtered.chunks_exact(stride + 1) {
+        let mut row = line[1..].to_vec();
+        for i in 0..stride {
+            let a = if i >= channels { row[i - channels] } else { 0 };
+            let b = prev[i];
+            let c = if i >= channels { prev[i - channels] } else { 0 };
+            let predictor = match line[0] {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => {
+                    let p = a as i16 + b as i16 - c as i16;
+                    let (pa, pb, pc) = (
+                        (p - a as i16).abs(),
+                        (p - b as i16).abs(),
+                        (p - c as i16).abs(),
+                    );
+                    if pa <= pb && pa <= pc {
+                        a
+                    } else if pb <= pc {
+                        b
+                    } else {
+                        c
+                    }
+                }
+                filter => panic!("unknown PNG filter {}", filter),
+            };
+            row[i] = row[i].wrapping_add(predictor);
+        }
+        for pixel in row.chunks_exact(channels) {
+            data.extend_from_slice(&pixel[..3]);
+            data.push(if channels == 4 { pixel[3] } else { 255 });
+        }
+        prev = row;
+    }
+    Image::from_data(width, height, ColorDepth::RGBA, data).unwrap()
+}
+
+/// every `name.aseprite` in `sample_aseprite_files/render` is compared
+/// pixel for pixel with the first frame Aseprite exported to `name.png`,
+/// with `aseprite -b name.aseprite --frame-range 0,0 --save-as name.png`
+#[test]
+fn render_references() -> std::io::Result<()> {
+    let dir = std::path::Path::new("sample_aseprite_files/render");
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension() != Some("aseprite".as_ref()) {
+            continue;
+        }
+        let reference = decode_png(&std::fs::read(path.with_extension("png"))?);
+        let sprite = read_sprite(path.to_str().unwrap())?;
+        let canvas = sprite.render_frame(0)?;
+        assert_eq!(
+            (canvas.width, canvas.height),
+            (reference.width, reference.height),
+            "{}",
+            path.display()
+        );
+        for y in 0..canvas.height {
+            for x in 0..canvas.width {
+                assert_eq!(
+                    canvas.get(x, y),
+                    reference.get(x, y),
+                    "{} at ({}, {})",
+                    path.display(),
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn png() -> std::io::Result<()> {
+    let idat = |chunks: &[(String, Vec<u8>)]| {
+        let (_, data) = chunks.iter().find(|(t, _)| t == "IDAT").unwrap();
+        helpers::zlib_decompress(data).unwrap()
+    };
+
+    let mut header = Header::new(2, 1);
+    header.pixel_width = 2;
+    header.pixel_height = 1;
+    let mut frame = Frame::new();
+    frame.add_chunk(Chunk::new(ChunkData::LayerChunk(LayerChunk::new(
+        "layer", true,
+    ))));
+    frame.add_chunk(Chunk::new(ChunkData::CelChunk(solid_cel(
+        0,
+        1,
+        0,
+        1,
+        1,
+        rgba(1, 2, 3, 4),
+    ))));
+    let sprite = Sprite::from_aseprite(Aseprite::new(header, vec![frame]));
+
+    let mut png = vec![];
+    sprite.write_frame_png(&mut png, 0)?;
+    let chunks = png_chunks(&png);
+    let types: Vec<_> = chunks.iter().map(|(t, _)| t.as_str()).collect();
+    assert_eq!(types, ["IHDR", "pHYs", "IDAT", "IEND"]);
+    assert_eq!(chunks[0].1, [0, 0, 0, 2, 0, 0, 0, 1, 8, 6, 0, 0, 0]);
+    assert_eq!(chunks[1].1, [0, 0, 0, 1, 0, 0, 0, 2, 0]);
+    assert_eq!(idat(&chunks), [0, 0, 0, 0, 0, 1, 2, 3, 4]);
+    assert_eq!(decode_png(&png), sprite.render_frame(0)?);
+
+    let mut png = vec![];
+    assert!(sprite.write_cel_png(&mut png, 0, 0)?);
+    assert_eq!(idat(&png_chunks(&png)), [0, 1, 2, 3, 4]);
+    assert!(!sprite.write_cel_png(&mut png, 1, 0)?);
+
+    let gray = Image::from_data(1, 2, ColorDepth::Grayscale, vec![9, 8, 7, 6])?;
+    let mut png = vec![];
+    write_png(&mut png, &gray, &PngOptions::default())?;
+    let chunks = png_chunks(&png);
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(chunks[0].1[9], 4);
+    assert_eq!(idat(&chunks), [0, 9, 8, 0, 7, 6]);
+
+    let palette = Palette {
+        colors: vec![
+            PaletteColor {
+                color: rgba(10, 20, 30, 255),
+                name: None,
+            },
+            PaletteColor {
+                color: rgba(40, 50, 60, 128),
+                name: None,
+            },
+            PaletteColor {
+                color: rgba(70, 80, 90, 255),
+                name: None,
+            },
+        ],
+    };
+    let indexed = Image::from_data(3, 1, ColorDepth::Indexed, vec![2, 1, 0])?;
+    let options = PngOptions {
+        palette: Some(&palette),
+        transparent_index: Some(0),
+        pixel_ratio: None,
+    };
+    let mut png = vec![];
+    write_png(&mut png, &indexed, &options)?;
+    let chunks = png_chunks(&png);
+    let types: Vec<_> = chunks.iter().map(|(t, _)| t.as_str()).collect();
+    assert_eq!(types, ["IHDR", "PLTE", "tRNS", "IDAT", "IEND"]);
+    assert_eq!(chunks[0].1[9], 3);
+    assert_eq!(chunks[1].1, [10, 20, 30, 40, 50, 60, 70, 80, 90]);
+    assert_eq!(chunks[2].1, [0, 128]);
+    assert_eq!(idat(&chunks), [0, 2, 1, 0]);
+
+    let mut png = vec![];
+    assert!(write_png(&mut png, &indexed, &PngOptions::default()).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn sprite_sheet() -> std::io::Result<()> {
+    let mut file =
+        std::fs::File::open("sample_aseprite_files/animated.aseprite")?;
+    let mut ase = Aseprite::from_read(&mut file)?;
+    ase.frames[0].add_chunk(Chunk::new(ChunkData::FrameTagsChunk(
+        FrameTagsChunk {
+            number_of_tags: 1,
+            tags: vec![Tag {
+                from_tag: 1,
+                to_tag: 3,
+                loop_animation_direction: LoopAnimationDirection::PingPong,
+                tag_color: RGB256 {
+                    r: 255,
+                    g: 0,
+                    b: 16,
+                },
+                tag_name: "walk \"fast\"".to_owned(),
+                user_data: None,
+            }],
+        },
+    )));
+    let sprite = Sprite::from_aseprite(ase);
+
+    let rects = |sheet: &SpriteSheet| {
+        sheet
+            .frames
+            .iter()
+            .map(|f| (f.x, f.y, f.width, f.height))
+            .collect::<Vec<_>>()
+    };
+    let layout = |sheet_type: SheetType, rows: u32| {
+        let options = SheetOptions {
+            sheet_type,
+            rows,
+            ..SheetOptions::default()
+        };
+        let sheet = sprite.sprite_sheet(&options).unwrap();
+        let positions: Vec<_> =
+            sheet.frames.iter().map(|f| (f.x / 32, f.y / 32)).collect();
+        ((sheet.image.width, sheet.image.height), positions)
+    };
+    assert_eq!(
+        layout(SheetType::Horizontal, 0),
+        ((128, 32), vec![(0, 0), (1, 0), (2, 0), (3, 0)])
+    );
+    assert_eq!(
+        layout(SheetType::Vertical, 0),
+        ((32, 128), vec![(0, 0), (0, 1), (0, 2), (0, 3)])
+    );
+    assert_eq!(
+        layout(SheetType::Rows, 0),
+        ((64, 64), vec![(0, 0), (1, 0), (0, 1), (1, 1)])
+    );
+    assert_eq!(
+        layout(SheetType::Columns, 3),
+        ((64, 96), vec![(0, 0), (0, 1), (0, 2), (1, 0)])
+    );
+    assert_eq!(
+        layout(SheetType::SmallestGrid, 0),
+        ((64, 64), vec![(0, 0), (1, 0), (0, 1), (1, 1)])
+    );
+
+    // packed frames are trimmed, don't overlap and keep their pixels
+    let packed = |options: &SheetOptions| {
+        let sheet = sprite.sprite_sheet(options).unwrap();
+        for (i, a) in sheet.frames.iter().enumerate() {
+            let rendered = sprite.render_frame(a.frame_index).unwrap();
+            let pad = options.inner_padding;
+            for y in 0..a.source_height {
+                for x in 0..a.source_width {
+                    assert_eq!(
+                        sheet.image.get(a.x + pad + x, a.y + pad + y),
+                        rendered.get(a.source_x + x, a.source_y + y)
+                    );
+                }
+            }
+            assert!(a.x + a.width <= sheet.image.width);
+            assert!(a.y + a.height <= sheet.image.height);
+            for b in &sheet.frames[i + 1..] {
+                let space = options.shape_padding + 2 * options.extrude as u32;
+                assert!(
+                    a.x + a.width + space <= b.x
+                        || b.x + b.width + space <= a.x
+                        || a.y + a.height + space <= b.y
+                        || b.y + b.height + space <= a.y,
+                    "{:?} overlaps {:?}",
+                    a,
+                    b
+                );
+            }
+        }
+        sheet
+    };
+    let options = SheetOptions {
+        sheet_type: SheetType::Packed,
+        ..SheetOptions::default()
+    };
+    let sheet = packed(&options);
+    assert!(sheet.frames.iter().all(|f| f.trimmed));
+    assert!(sheet.image.width * sheet.image.height < 64 * 64);
+    for frame in &sheet.frames {
+        let rendered = sprite.render_frame(frame.frame_index)?;
+        let opaque = (0..32 * 32)
+            .filter(|i| match rendered.get(i % 32, i / 32) {
+                Some(Pixel::RGBA(c)) => c.a != 0,
+                _ => false,
+            })
+            .count();
+        let trimmed = (0..frame.source_width * frame.source_height)
+            .filter(|i| {
+                let x = frame.source_x + i % frame.source_width;
+                let y = frame.source_y + i / frame.source_width;
+                match rendered.get(x, y) {
+                    Some(Pixel::RGBA(c)) => c.a != 0,
+                    _ => false,
+                }
+            })
+            .count();
+        assert_eq!(opaque, trimmed);
+    }
+    let json = sheet.json(JsonFormat::Hash);
+    let first = &sheet.frames[0];
+    assert!(json.contains("    \"trimmed\": true,\n"));
+    assert!(json.contains(&format!(
+        "    \"spriteSourceSize\": {{ \"x\": {}, \"y\": {}, \"w\": {}, \"h\": {} }},\n",
+        first.source_x, first.source_y, first.source_width, first.source_height
+    )));
+    packed(&SheetOptions {
+        border_padding: 3,
+        shape_padding: 2,
+        inner_padding: 1,
+        extrude: true,
+        ..options.clone()
+    });
+
+    // paddings that overflow the sheet size are errors
+    for options in [
+        SheetOptions {
+            border_padding: u32::MAX / 2 + 1,
+            ..SheetOptions::default()
+        },
+        SheetOptions {
+            sheet_type: SheetType::SmallestGrid,
+            shape_padding: u32::MAX,
+            ..SheetOptions::default()
+        },
+        SheetOptions {
+            inner_padding: u32::MAX / 2,
+            ..SheetOptions::default()
+        },
+    ] {
+        match sprite.sprite_sheet(&options) {
+            Err(Error::InvalidData { message, .. }) => {
+                assert!(message.contains("32 bits"), "{}", message)
+            }
+            other => panic!("expected an overflow error, got {:?}", other),
+        }
+    }
+
+    let sheet = sprite.sprite_sheet(&SheetOptions {
+        border_padding: 2,
+        shape_padding: 1,
+        inner_padding: 1,
+        extrude: true,
+        ..SheetOptions::default()
+    })?;
+    assert_eq!((sheet.image.width, sheet.image.height), (151, 40));
+    assert_eq!(rects(&sheet)[..2], [(3, 3, 34, 34), (40, 3, 34, 34)]);
+
+    let sheet = sprite.sprite_sheet(&SheetOptions {
+        extrude: true,
+        ..SheetOptions::default()
+    })?;
+    let frame = sprite.render_frame(1)?;
+    let (x, y) = (sheet.frames[1].x, sheet.frames[1].y);
+    for i in 0..32 {
+        assert_eq!(sheet.image.get(x + i, y + 5), frame.get(i, 5));
+        assert_eq!(sheet.image.get(x - 1, y + i), frame.get(0, i));
+        assert_eq!(sheet.image.get(x + 32, y + i), frame.get(31, i));
+        assert_eq!(sheet.image.get(x + i, y - 1), frame.get(i, 0));
+    }
+
+    let sheet = sprite.sprite_sheet(&SheetOptions {
+        filename: "hero.ase".to_owned(),
+        image: "hero.png".to_owned(),
+        ..SheetOptions::default()
+    })?;
+    let hash = sheet.json(JsonFormat::Hash);
+    assert!(hash.starts_with("{ \"frames\": {\n   \"hero 0.ase\": {\n"));
+    assert!(hash.contains(
+        "    \"frame\": { \"x\": 96, \"y\": 0, \"w\": 32, \"h\": 32 },\n"
+    ));
+    assert!(hash.contains("    \"duration\": 100\n"));
+    assert!(hash.contains("  \"image\": \"hero.png\",\n"));
+    assert!(hash.contains("  \"size\": { \"w\": 128, \"h\": 32 },\n"));
+    assert!(hash.contains(
+        "   { \"name\": \"walk \\\"fast\\\"\", \"from\": 1, \"to\": 3, \
+         \"direction\": \"pingpong\", \"color\": \"#ff0010ff\" }\n"
+    ));
+    let array = sheet.json(JsonFormat::Array);
+    assert!(array.starts_with(
+        "{ \"frames\": [\n   {\n    \"filename\": \"hero 0.ase\",\n"
+    ));
+    assert_eq!(
+        array.matches("\"filename\"").count(),
+        hash.matches("\"rotated\"").count()
+    );
+
+    let sliced = read_sprite("sample_aseprite_files/sliced.aseprite")?;
+    let json = sliced
+        .sprite_sheet(&SheetOptions::default())?
+        .json(JsonFormat::Hash);
+    assert!(json.contains("   \"sprite.aseprite\": {\n"));
+    assert!(json.contains(
+        "   { \"name\": \"Slice\", \"color\": \"#0000ffff\", \"keys\": [{ \
+         \"frame\": 0, \"bounds\": {\"x\": 0, \"y\": 0, \"w\": 32, \"h\": 32 }, \
+         \"center\": {\"x\": 14, \"y\": 14, \"w\": 4, \"h\": 4 }, \
+         \"pivot\": {\"x\": 11, \"y\": 11 } }] }\n"
+    ));
+
+    let mut png = vec![];
+    sheet.write_png(&mut png)?;
+    assert_eq!(png_chunks(&png)[0].1[..8], [0, 0, 0, 128, 0, 0, 0, 32]);
+
+    Ok(())
+}
+
+fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1usize << min_code_size;
+    let eoi = clear + 1;
+    let reset =
+        || -> Vec<Vec<u8>> { (0..clear + 2).map(|i| vec![i as u8]).collect() };
+    let mut table = reset();
+    let mut width = min_code_size as u32 + 1;
+    let mut prev: Option<Vec<u8>> = None;
+    let mut out = vec![];
+    let (mut bits, mut bit_count, mut bytes) = (0u32, 0u32, data.iter());
+    loop {
+        while bit_count < width {
+            bits |= (*bytes.next().unwrap() as u32) << bit_count;
+            bit_count += 8;
+        }
+        let code = (bits & ((1 << width) - 1)) as usize;
+        bits >>= width;
+        bit_count -= width;
+
+        if code == clear {
+            table = reset();
+            width = min_code_size as u32 + 1;
+            prev = None;
+            continue;
+        } else if code == eoi {
+            return out;
+        }
+        let entry = match (table.get(code), &prev) {
+            (Some(entry), _) => entry.clone(),
+            (None, Some(prev)) if code == table.len() => {
+                let mut entry = prev.clone();
+                entry.push(prev[0]);
+                entry
+            }
+            _ => panic!("invalid LZW code {}", code),
+        };
+        out.extend_from_slice(&entry);
+        if let Some(mut prev) = prev.take() {
+            if table.len() < 4096 {
+                prev.push(entry[0]);
+                table.push(prev);
+            }
+        }
+        if table.len() == 1 << width && width < 12 {
+            width += 1;
+        }
+        prev = Some(entry);
+    }
+}
+
+/// decode the frames of a GIF written by `Sprite::write_gif`, as their
+/// delay and RGBA pixels
+fn gif_frames(gif: &[u8]) -> Vec<(u16, Vec<RGBA256>)> {
+    use byteorder::{LittleEndian, ReadBytesExt};
+
+    assert_eq!(&gif[..6], b"GIF89a");
+    let mut rdr = Cursor::new(&gif[6..]);
+    let width = rdr.read_u16::<LittleEndian>().unwrap() as usize;
+    let height = rdr.read_u16::<LittleEndian>().unwrap() as usize;
+    assert_eq!(rdr.read_u8().unwrap() & 0x80, 0);
+    rdr.seek(SeekFrom::Current(2)).unwrap();
+
+    let sub_blocks = |rdr: &mut Cursor<&[u8]>| {
+        let mut data = vec![];
+        loop {
+            let len = rdr.read_u8().unwrap() as usize;
+            if len == 0 {
+                return data;
+            }
+            let start = data.len();
+            data.resize(start + len, 0);
+            rdr.read_exact(&mut data[start..]).unwrap();
+        }
+    };
+
+    let mut frames = vec![];
+    let (mut delay, mut transparent) = (0, None);
+    loop {
+        match rdr.read_u8().unwrap() {
+            0x21 => {
+                let label = rdr.read_u8().unwrap();
+                let data = sub_blocks(&mut rdr);
+                if label == 0xF9 {
+                    delay = u16::from_le_bytes([data[1], data[2]]);
+                    transparent = if data[0] & 1 == 1 {
+                        Some(data[3])
+                    } else {
+                        None
+                    };
+                }
+            }
+            0x2C => {
+                rdr.seek(SeekFrom::Current(4)).unwrap();
+                assert_eq!(
+                    rdr.read_u16::<LittleEndian>().unwrap() as usize,
+                    width
+                );
+                assert_eq!(
+                    rdr.read_u16::<LittleEndian>().unwrap() as usize,
+                    height
+                );
+                let packed = rdr.read_u8().unwrap();
+                assert_eq!(packed & 0x80, 0x80);
+                let mut table = vec![0; 3 << ((packed & 7) + 1)];
+                rdr.read_exact(&mut table).unwrap();
+                let min_code_size = rdr.read_u8().unwrap();
+                let indices = lzw_decode(&sub_blocks(&mut rdr), min_code_size);
+                assert_eq!(indices.len(), width * height);
+                let pixels = indices
+                    .iter()
+                    .map(|&i| {
+                        if Some(i) == transparent {
+                            rgba(0, 0, 0, 0)
+                        } else {
+                            let c = &table[i as usize * 3..i as usize * 3 + 3];
+                            rgba(c[0], c[1], c[2], 255)
+                        }
+                    })
+                    .collect();
+                frames.push((delay, pixels));
+            }
+            0x3B => return frames,
+            other => panic!("unexpected GIF block 0x{:X}", other),
+        }
+    }
+}
+
+#[test]
+fn gif() -> std::io::Result<()> {
+    // opaque render pixels as GIF writes them
+    let rendered = |sprite: &Sprite, frame: usize| -> Vec<RGBA256> {
+        let image = sprite.render_frame(frame).unwrap();
+        image
+            .data
+            .chunks_exact(4)
+            .map(|p| match p[3] {
+                0..=127 => rgba(0, 0, 0, 0),
+                _ => rgba(p[0], p[1], p[2], 255),
+            })
+            .collect()
+    };
+
+    let mut file =
+        std::fs::File::open("sample_aseprite_files/animated.aseprite")?;
+    let mut ase = Aseprite::from_read(&mut file)?;
+    ase.frames[1].frame_duration_milliseconds = 35;
+    let tag = |name: &str, direction| Tag {
+        from_tag: 1,
+        to_tag: 3,
+        loop_animation_direction: direction,
+        tag_color: RGB256::default(),
+        tag_name: name.to_owned(),
+        user_data: None,
+    };
+    ase.frames[0].add_chunk(Chunk::new(ChunkData::FrameTagsChunk(
+        FrameTagsChunk {
+            number_of_tags: 2,
+            tags: vec![
+                tag("reverse", LoopAnimationDirection::Reverse),
+                tag("pingpong", LoopAnimationDirection::PingPong),
+            ],
+        },
+    )));
+    let sprite = Sprite::from_aseprite(ase);
+
+    let mut gif = vec![];
+    sprite.write_gif(&mut gif)?;
+    let frames = gif_frames(&gif);
+    let delays: Vec<_> = frames.iter().map(|(delay, _)| *delay).collect();
+    assert_eq!(delays, [10, 4, 10, 10]);
+    for (i, (_, pixels)) in frames.iter().enumerate() {
+        assert_eq!(pixels, &rendered(&sprite, i));
+    }
+
+    for (name, sequence) in
+        [("reverse", vec![3, 2, 1]), ("pingpong", vec![1, 2, 3, 2])]
+    {
+        let mut gif = vec![];
+        sprite.write_tag_gif(&mut gif, name)?;
+        let frames = gif_frames(&gif);
+        assert_eq!(frames.len(), sequence.len());
+        for ((_, pixels), i) in frames.iter().zip(sequence) {
+            assert_eq!(pixels, &rendered(&sprite, i));
+        }
+    }
+    assert!(sprite.write_tag_gif(&mut vec![], "missing").is_err());
+
+    // more codes than the LZW table holds, with 200 exact colors
+    let mut seed = 1u32;
+    let mut random = || {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16) as u8
+    };
+    let colors: Vec<_> = (0..200)
+        .map(|_| rgba(random(), random(), random(), 255))
+        .collect();
+    let pixels: Vec<_> = (0..128 * 128)
+        .map(|_| colors[random() as usize % colors.len()])
+        .collect();
+    let mut frame = Frame::new();
+    frame.add_chunk(Chunk::new(ChunkData::LayerChunk(LayerChunk::new(
+        "noise", true,
+    ))));
+    frame.add_chunk(Chunk::new(ChunkData::CelChunk(CelChunk::new(
+        0,
+        0,
+        0,
+        128,
+        128,
+        Pixels::RGBA(pixels.clone()),
+    ))));
+    let noise = Sprite::from_aseprite(Aseprite::new(
+        Header::new(128, 128),
+        vec![frame],
+    ));
+    let mut gif = vec![];
+    noise.write_gif(&mut gif)?;
+    assert_eq!(gif_frames(&gif)[0].1, pixels);
+
+    // a gradient with more than 255 colors is quantized
+    let pixels: Vec<_> = (0..32 * 32)
+        .map(|i| rgba((i % 32 * 8) as u8, (i / 32 * 8) as u8, 100, 255))
+        .collect();
+    let mut frame = Frame::new();
+    frame.add_chunk(Chunk::new(ChunkData::LayerChunk(LayerChunk::new(
+        "gradient", true,
+    ))));
+    frame.add_chunk(Chunk::new(ChunkData::CelChunk(CelChunk::new(
+        0,
+        0,
+        0,
+        32,
+        32,
+        Pixels::RGBA(pixels.clone()),
+    ))));
+    let gradient =
+        Sprite::from_aseprite(Aseprite::new(Header::new(32, 32), vec![frame]));
+    let mut gif = vec![];
+    gradient.write_gif(&mut gif)?;
+    let decoded = &gif_frames(&gif)[0].1;
+    for (a, b) in pixels.iter().zip(decoded) {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).abs();
+        assert!(d(a.r, b.r) <= 12 && d(a.g, b.g) <= 12 && a.b == b.b);
+    }
+
+    // indexed sprites keep their palette and transparent index
+    let mut header = Header::new(3, 1);
+    header.color_depth = ColorDepth::Indexed;
+    header.transparent_palette_entry = 1;
+    let mut frame = Frame::new();
+    frame.add_chunk(Chunk::new(ChunkData::PaletteChunk(PaletteChunk {
+        new_palette_size: 3,
+        first_color_index_to_change: 0,
+        last_color_index_to_change: 2,
+        palette_entries: vec![
+            palette_entry(rgba(10, 20, 30, 255), None),
+            palette_entry(rgba(0, 0, 0, 255), None),
+            palette_entry(rgba(40, 50, 60, 255), None),
+        ],
+        user_data: None,
+    })));
+    frame.add_chunk(Chunk::new(ChunkData::LayerChunk(LayerChunk::new(
+        "layer", true,
+    ))));
+    frame.add_chunk(Chunk::new(ChunkData::CelChunk(CelChunk::new(
+        0,
+        0,
+        0,
+        3,
+        1,
+        Pixels::Indexed(vec![2, 1, 0]),
+    ))));
+    let indexed = Sprite::from_aseprite(Aseprite::new(header, vec![frame]));
+    let mut gif = vec![];
+    indexed.write_gif(&mut gif)?;
+    assert_eq!(
+        gif_frames(&gif)[0].1,
+        [
+            rgba(40, 50, 60, 255),
+            rgba(0, 0, 0, 0),
+            rgba(10, 20, 30, 255)
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn timeline() {
+    let mut frames: Vec<_> = (0..6).map(|_| Frame::new()).collect();
+    for (i, frame) in frames.iter_mut().enumerate() {
+        frame.frame_duration_milliseconds = 10 * (i as u16 + 1);
+    }
+    let tag = |name: &str, from_tag, to_tag, direction| Tag {
+        from_tag,
+        to_tag,
+        loop_animation_direction: direction,
+        tag_color: RGB256::default(),
+        tag_name: name.to_owned(),
+        user_data: None,
+    };
+    frames[0].add_chunk(Chunk::new(ChunkData::FrameTagsChunk(
+        FrameTagsChunk {
+            number_of_tags: 5,
+            tags: vec![
+                tag("forward", 1, 3, LoopAnimationDirection::Forward),
+                tag("reverse", 1, 3, LoopAnimationDirection::Reverse),
+                tag("pingpong", 1, 4, LoopAnimationDirection::PingPong),
+                tag("single", 2, 2, LoopAnimationDirection::PingPong),
+                tag("outside", 4, 9, LoopAnimationDirection::Forward),
+            ],
+        },
+    )));
+    let sprite =
+        Sprite::from_aseprite(Aseprite::new(Header::new(1, 1), frames));
+
+    let forward = sprite.timeline("forward").unwrap();
+    assert_eq!(forward.frames, [(1, 20), (2, 30), (3, 40)]);
+    assert_eq!(forward.duration(Playback::Loop), 90);
+    let reverse = sprite.timeline("reverse").unwrap();
+    assert_eq!(reverse.frames, [(3, 40), (2, 30), (1, 20)]);
+
+    let pingpong = sprite.timeline("pingpong").unwrap();
+    assert_eq!(
+        pingpong.frames,
+        [(1, 20), (2, 30), (3, 40), (4, 50), (3, 40), (2, 30)]
+    );
+    assert_eq!(
+        pingpong.one_shot_frames(),
+        [
+            (1, 20),
+            (2, 30),
+            (3, 40),
+            (4, 50),
+            (3, 40),
+            (2, 30),
+            (1, 20)
+        ]
+    );
+    assert_eq!(pingpong.duration(Playback::Loop), 210);
+    assert_eq!(pingpong.duration(Playback::OneShot), 230);
+
+    let single = sprite.timeline("single").unwrap();
+    assert_eq!(single.frames, [(2, 30)]);
+    assert_eq!(single.one_shot_frames(), [(2, 30)]);
+    assert_eq!(
+        sprite.timeline("outside").unwrap().frames,
+        [(4, 50), (5, 60)]
+    );
+    assert!(sprite.timeline("missing").is_none());
+
+    let at = |timeline: &Timeline, time, playback| {
+        timeline.frame_at(time, playback).unwrap()
+    };
+    assert_eq!(at(&forward, 0, Playback::Loop), 1);
+    assert_eq!(at(&forward, 19, Playback::Loop), 1);
+    assert_eq!(at(&forward, 20, Playback::Loop), 2);
+    assert_eq!(at(&forward, 89, Playback::Loop), 3);
+    assert_eq!(at(&forward, 90, Playback::Loop), 1);
+    assert_eq!(at(&forward, 90, Playback::OneShot), 3);
+    assert_eq!(at(&forward, 1000, Playback::OneShot), 3);
+    assert_eq!(at(&reverse, 45, Playback::Loop), 2);
+
+    // 20 + 30 + 40 + 50 + 40 + 30 = 210 ms per loop
+    assert_eq!(at(&pingpong, 139, Playback::Loop), 4);
+    assert_eq!(at(&pingpong, 140, Playback::Loop), 3);
+    assert_eq!(at(&pingpong, 180, Playback::Loop), 2);
+    assert_eq!(at(&pingpong, 210, Playback::Loop), 1);
+    assert_eq!(at(&pingpong, 210, Playback::OneShot), 1);
+    assert_eq!(at(&pingpong, 500, Playback::OneShot), 1);
+    assert_eq!(at(&single, 12345, Playback::Loop), 2);
+
+    let empty = Timeline {
+        direction: LoopAnimationDirection::Forward,
+        frames: vec![],
+    };
+    assert_eq!(empty.frame_at(0, Playback::Loop), None);
+}
+
+#[test]
+fn write_counts() -> std::io::Result<()> {
+    // 0xFFFF chunks don't fit in the old count, which tells to read the new
+    let mut frame = Frame::new();
+    for _ in 0..u16::MAX {
+        frame.add_chunk(Chunk::new(ChunkData::UserDataChunk(
+            UserDataChunk::default(),
+        )));
+    }
+    let mut bytes = vec![];
+    frame.write(&mut bytes)?;
+    assert_eq!(bytes[6..8], u16::MAX.to_le_bytes());
+    assert_eq!(bytes[12..16], 0xFFFFu32.to_le_bytes());
+    let frame = Frame::from_read(&mut Cursor::new(bytes), &Header::default())?;
+    assert_eq!(frame.chunks.len(), u16::MAX as usize);
+
+    let too_long = |result: Result<(), Error>, what: &str| match result {
+        Err(Error::InvalidData { message, .. }) => {
+            assert!(message.contains(what), "{}", message)
+        }
+        other => panic!("expected {} error, got {:?}", what, other),
+    };
+    let frames = (0..=u16::MAX as usize).map(|_| Frame::new()).collect();
+    too_long(
+        Aseprite::new(Header::new(1, 1), frames).write(&mut vec![]),
+        "frames",
+    );
+    let layer = LayerChunk::new(&"x".repeat(1 << 16), true);
+    too_long(
+        Chunk::new(ChunkData::LayerChunk(layer)).write(&mut vec![]),
+        "String",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn errors() -> std::io::Result<()> {
+    let mut frames = vec![];
+    for _ in 0..2 {
+        let mut frame = Frame::new();
+        frame
+            .add_chunk(Chunk::new(ChunkData::LayerChunk(LayerChunk::new(
+                "layer", true,
+            ))))
+            .add_chunk(Chunk::new(ChunkData::CelChunk(solid_cel(
+                0,
+                0,
+                0,
+                1,
+                1,
+                rgba(255, 0, 0, 255),
+            ))));
+        frames.push(frame);
+    }
+    let mut wtr = Cursor::new(vec![]);
+    Aseprite::new(Header::default(), frames).write(&mut wtr)?;
+    let bytes = wtr.into_inner();
+
+    let u32_at = |offset: usize| {
+        u32::from_le_bytes([
+            bytes[offset],
+            bytes[offset + 1],
+            bytes[offset + 2],
+            bytes[offset + 3],
+        ]) as usize
+    };
+    let frame1 = 128 + u32_at(128);
+    let layer1 = frame1 + 16;
+    let cel1 = layer1 + u32_at(layer1);
+
+    let read = |bytes: &[u8]| Aseprite::from_read(&mut Cursor::new(bytes));
+
+    match read(&bytes[..10]) {
+        Err(Error::Truncated { location }) => assert_eq!(
+            location,
+            Location {
+                offset: Some(0),
+                frame: None,
+                chunk: None
+            }
+        ),
+        other => panic!("expected truncated header, got {:?}", other),
+    }
+
+    let set_u32 = |bytes: &mut Vec<u8>, offset: usize, value: usize| {
+        bytes[offset..offset + 4].copy_from_slice(&(value as u32).to_le_bytes())
+    };
+
+    // the header says the file is longer than the stream
+    let mut truncated = bytes[..cel1 + 10].to_vec();
+    match read(&truncated) {
+        Err(Error::Truncated { location }) => {
+            assert_eq!(location.offset, Some(0));
+            assert_eq!(location.frame, None);
+        }
+        other => panic!("expected truncated file, got {:?}", other),
+    }
+
+    // the frame is longer than the stream
+    let len = truncated.len();
+    set_u32(&mut truncated, 0, len);
+    match read(&truncated) {
+        Err(Error::Truncated { location }) => assert_eq!(
+            location,
+            Location {
+                offset: Some(frame1 as u64),
+                frame: Some(1),
+                chunk: None
+            }
+        ),
+        other => panic!("expected truncated frame, got {:?}", other),
+    }
+
+    // the cel is longer than the frame
+    set_u32(&mut truncated, frame1, len - frame1);
+    match read(&truncated) {
+        Err(Error::Truncated { location }) => assert_eq!(
+            location,
+            Location {
+                offset: Some(cel1 as u64),
+                frame: Some(1),
+                chunk: Some(1)
+            }
+        ),
+        other => panic!("expected truncated cel, got {:?}", other),
+    }
+
+    let mut bad_magic = bytes.clone();
+    bad_magic[4] = 0;
+    match read(&bad_magic) {
+        Err(Error::BadMagic {
+            expected: 0xA5E0,
+            found: 0xA500,
+            location,
+        }) => assert_eq!(location.offset, Some(4)),
+        other => panic!("expected bad file magic, got {:?}", other),
+    }
+    let err = read(&bytes[1..]).unwrap_err();
+    assert!(matches!(err, Error::BadMagic { .. }), "{:?}", err);
+
+    let mut bad_magic = bytes.clone();
+    bad_magic[frame1 + 4] = 0;
+    match read(&bad_magic) {
+        Err(Error::BadMagic {
+            expected: 0xF1FA,
+            found: 0xF100,
+            location,
+        }) => assert_eq!(
+            location,
+            Location {
+                offset: Some(frame1 as u64 + 4),
+                frame: Some(1),
+                chunk: None
+            }
+        ),
+        other => panic!("expected bad frame magic, got {:?}", other),
+    }
+
+    let mut concatenated = bytes.clone();
+    concatenated.extend_from_slice(&bytes);
+    match read(&concatenated) {
+        Err(Error::InvalidData { location, .. }) => {
+            assert_eq!(location.offset, Some(0))
+        }
+        other => panic!("expected invalid file size, got {:?}", other),
+    }
+
+    let mut trailing = bytes.clone();
+    trailing.extend_from_slice(&[0; 4]);
+    set_u32(&mut trailing, 0, bytes.len() + 4);
+    match read(&trailing) {
+        Err(Error::InvalidData { location, .. }) => {
+            assert_eq!((location.offset, location.frame), (Some(0), None))
+        }
+        other => panic!("expected invalid file size, got {:?}", other),
+    }
+
+    // the frame header says the frame is bigger than its chunks
+    let mut padded = bytes.clone();
+    padded.extend_from_slice(&[0; 4]);
+    set_u32(&mut padded, 0, bytes.len() + 4);
+    set_u32(&mut padded, frame1, bytes.len() + 4 - frame1);
+    match read(&padded) {
+        Err(Error::InvalidData { location, .. }) => assert_eq!(
+            (location.offset, location.frame),
+            (Some(frame1 as u64), Some(1))
+        ),
+        other => panic!("expected invalid frame size, got {:?}", other),
+    }
+
+    let mut unknown = bytes.clone();
+    unknown[cel1 + 4..cel1 + 6].copy_from_slice(&0x1234u16.to_le_bytes());
+    let err = read(&unknown).unwrap_err();
+    match &err {
+        Error::UnknownChunkType {
+            chunk_type: 0x1234,
+            location,
+        } => assert_eq!(
+            *location,
+            Location {
+                offset: Some(cel1 as u64),
+                frame: Some(1),
+                chunk: Some(1)
+            }
+        ),
+        other => panic!("expected unknown chunk type, got {:?}", other),
+    }
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Invalid Chunk Type 0x1234 (frame 1, chunk 1, offset 0x{:X})",
+            cel1
+        )
+    );
+    let err: std::io::Error = err.into();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // layer type of the layer in frame 1
+    let mut invalid_enum = bytes.clone();
+    invalid_enum[layer1 + 8] = 9;
+    match read(&invalid_enum) {
+        Err(Error::InvalidEnumValue { location, .. }) => {
+            assert_eq!((location.frame, location.chunk), (Some(1), Some(0)))
+        }
+        other => panic!("expected invalid enum value, got {:?}", other),
+    }
+
+    // first byte of the layer name
+    let mut invalid_utf8 = bytes.clone();
+    invalid_utf8[layer1 + 6 + 18] = 0xFF;
+    match read(&invalid_utf8) {
+        Err(Error::InvalidUtf8 { location, .. }) => {
+            assert_eq!(location.offset, Some(layer1 as u64))
+        }
+        other => panic!("expected invalid utf-8, got {:?}", other),
+    }
+
+    let cel = Cel::CompressedImage {
+        width: 1,
+        height: 1,
+        zlib_compressed_data: vec![1, 2, 3],
+    };
+    assert!(matches!(
+        cel.pixels(&ColorDepth::RGBA),
+        Err(Error::Decompression { .. })
+    ));
+
+    Ok(())
+}
+
+/// xorshift64, the mutations are the same on every run
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// read `bytes` and use everything that is decoded lazily, nothing here may
+/// panic whatever the bytes are
+fn exercise(bytes: &[u8], options: &ReadOptions) {
+    let ase = match Aseprite::from_read_with_options(
+        &mut Cursor::new(bytes),
+        options,
+    ) {
+        Ok(ase) => ase,
+        Err(_) => return,
+    };
+    let color_depth = ase.header.color_depth;
+    for chunk in ase.frames.iter().flat_map(|frame| frame.chunks.iter()) {
+        match &chunk.chunk_data {
+            ChunkData::CelChunk(cel) => {
+                let _ = cel.image(&color_depth);
+                let _ = cel.cel.pixels(&color_depth);
+                let _ = cel.cel.tilemap();
+            }
+            ChunkData::TilesetChunk(tileset) => {
+                let _ = tileset.image(&color_depth);
+            }
+            _ => (),
+        }
+    }
+
+    let sprite = Sprite::from_aseprite_with_limits(ase, options.limits);
+    let _ = sprite.layer_tree();
+    for frame in 0..sprite.frame_count() {
+        let _ = sprite.render_frame_srgb(frame);
+        let _ = sprite.palette_at(frame);
+    }
+    let _ = sprite.aseprite().write(&mut Cursor::new(vec![]));
+}
+
+#[test]
+fn mutated_files() -> std::io::Result<()> {
+    let options = ReadOptions {
+        limits: Limits {
+            max_width: 256,
+            max_height: 256,
+            max_frames: 64,
+            max_chunks: 256,
+            max_palette_size: 256,
+            max_decompressed_size: 1 << 20,
+            max_image_size: 1 << 20,
+        },
+        ..ReadOptions::default()
+    };
+    let lenient = ReadOptions {
+        mode: ReadMode::Lenient,
+        ..options
+    };
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    for fname in &[
+        "sample_aseprite_files/simple.aseprite",
+        "sample_aseprite_files/layered.aseprite",
+        "sample_aseprite_files/animated.aseprite",
+        "sample_aseprite_files/sliced.aseprite",
+    ] {
+        let original = std::fs::read(fname)?;
+        exercise(&original, &options);
+        exercise(&original, &lenient);
+        for _ in 0..1000 {
+            let mut bytes = original.clone();
+            for _ in 0..1 + rng.below(4) {
+                let i = rng.below(bytes.len());
+                match rng.below(4) {
+                    0 => bytes[i] = rng.next() as u8,
+                    1 => bytes[i] ^= 1 << rng.below(8),
+                    2 => {
+                        let end = (i + 4).min(bytes.len());
+                        for b in &mut bytes[i..end] {
+                            *b = 0xFF;
+                        }
+                    }
+                    _ => bytes.truncate(i.max(1)),
+                }
+            }
+            // keep the file size right most of the time so the mutations
+            // reach the chunks
+            if bytes.len() >= 4 && rng.below(4) != 0 {
+                let len = bytes.len() as u32;
+                bytes[..4].copy_from_slice(&len.to_le_bytes());
+            }
+            exercise(&bytes, &options);
+            exercise(&bytes, &lenient);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn limits() -> std::io::Result<()> {
+    let animated = std::fs::read("sample_aseprite_files/animated.aseprite")?;
+    let read = |limits: Limits| {
+        let options = ReadOptions {
+            limits,
+            ..ReadOptions::default()
+        };
+        Aseprite::from_read_with_options(&mut Cursor::new(&animated), &options)
+    };
+    assert!(read(Limits::default()).is_ok());
+    for limits in [
+        Limits {
+            max_width: 8,
+            ..Limits::default()
+        },
+        Limits {
+            max_frames: 3,
+            ..Limits::default()
+        },
+        Limits {
+            max_chunks: 1,
+            ..Limits::default()
+        },
+        Limits {
+            max_palette_size: 8,
+            ..Limits::default()
+        },
+        Limits {
+            max_decompressed_size: 16,
+            ..Limits::default()
+        },
+        Limits {
+            max_image_size: 32 * 32 * 4 - 1,
+            ..Limits::default()
+        },
+    ] {
+        match read(limits) {
+            Err(Error::InvalidData { message, .. }) => {
+                assert!(message.contains("over the limit"), "{}", message)
+            }
+            other => panic!("expected {:?} to be hit, got {:?}", limits, other),
+        }
+    }
+
+    // the largest header is read, but rendering it would take 16 GiB
+    let mut huge = vec![];
+    Aseprite::new(Header::new(u16::MAX, u16::MAX), vec![Frame::new()])
+        .write(&mut huge)?;
+    fn over_the_limit<T>(result: Result<T, Error>) {
+        match result {
+            Err(Error::InvalidData { message, .. }) => {
+                assert!(message.contains("over the limit"), "{}", message)
+            }
+            Err(e) => panic!("expected the image size limit, got {:?}", e),
+            Ok(_) => panic!("expected the image size limit"),
+        }
+    }
+    over_the_limit(Aseprite::from_read(&mut Cursor::new(&huge)));
+    exercise(&huge, &ReadOptions::default());
+    let sprite = Sprite::from_aseprite(Aseprite::from_read_with_options(
+        &mut Cursor::new(&huge),
+        &ReadOptions {
+            limits: Limits {
+                max_image_size: u64::MAX,
+                ..Limits::default()
+            },
+            ..ReadOptions::default()
+        },
+    )?);
+    over_the_limit(sprite.render_frame(0));
+    over_the_limit(sprite.sprite_sheet(&SheetOptions::default()));
+    over_the_limit(sprite.write_gif(&mut vec![]));
+
+    let header = Header::default();
+    let chunk = |chunk_type: u16, data: &[u8]| {
+        let mut bytes = ((data.len() + 6) as u32).to_le_bytes().to_vec();
+        bytes.extend_from_slice(&chunk_type.to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    };
+
+    let mut too_small = chunk(0x2004, &[]);
+    too_small[..4].copy_from_slice(&3u32.to_le_bytes());
+    assert!(matches!(
+        Chunk::from_read(&mut Cursor::new(too_small), &header),
+        Err(Error::InvalidData { .. })
+    ));
+
+    // a huge count fails on the missing data instead of allocating first
+    let mut slice = u32::MAX.to_le_bytes().to_vec();
+    slice.extend_from_slice(&[0; 10]);
+    assert!(matches!(
+        Chunk::from_read(&mut Cursor::new(chunk(0x2022, &slice)), &header),
+        Err(Error::Truncated { .. })
+    ));
+
+    // palettes with fewer entries than their color indexes, or with the
+    // indexes backwards, are rejected without allocating the entries
+    let palette = |first: u32, last: u32| {
+        let mut palette = vec![];
+        for value in &[256u32, first, last, 0, 0] {
+            palette.extend_from_slice(&value.to_le_bytes());
+        }
+        palette.extend_from_slice(&[0, 0, 1, 2, 3, 255]);
+        chunk(0x2019, &palette)
+    };
+    match Chunk::from_read(&mut Cursor::new(palette(0, u32::MAX)), &header) {
+        Err(Error::InvalidData { message, .. }) => {
+            assert!(message.contains("over the limit"), "{}", message)
+        }
+        other => panic!("expected the palette size limit, got {:?}", other),
+    }
+    let mut entries = Cursor::new(palette(1, u32::MAX));
+    entries.set_position(6);
+    assert!(matches!(
+        PaletteChunk::from_read(&mut entries, 26),
+        Err(Error::Truncated { .. })
+    ));
+    assert!(matches!(
+        Chunk::from_read(&mut Cursor::new(palette(1, 0)), &header),
+        Err(Error::InvalidData { .. })
+    ));
+    assert!(Chunk::from_read(&mut Cursor::new(palette(3, 3)), &header).is_ok());
+
+    // a backwards palette in a file is a warning when reading leniently
+    let mut simple = std::fs::read("sample_aseprite_files/simple.aseprite")?;
+    let at = simple
+        .windows(2)
+        .position(|window| window == [0x19, 0x20])
+        .expect("simple.aseprite has a palette");
+    simple[at + 6..at + 10].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(matches!(
+        Aseprite::from_read(&mut Cursor::new(&simple)),
+        Err(Error::InvalidData { .. })
+    ));
+    let ase = Aseprite::from_read_with_options(
+        &mut Cursor::new(&simple),
+        &ReadOptions {
+            mode: ReadMode::Lenient,
+            ..ReadOptions::default()
+        },
+    )?;
+    assert_eq!(ase.warnings.len(), 1);
+
+    // limits are checked as soon as the sizes are read, before the data
+    let limited = ReadOptions {
+        limits: Limits {
+            max_width: 8,
+            max_palette_size: 8,
+            ..Limits::default()
+        },
+        ..ReadOptions::default()
+    };
+    let mut cel = vec![0; 9];
+    cel[7..9].copy_from_slice(&2u16.to_le_bytes()); // compressed image
+    cel.extend_from_slice(&[0; 7]);
+    cel.extend_from_slice(&16u16.to_le_bytes()); // width
+    cel.extend_from_slice(&1u16.to_le_bytes()); // height
+    let mut tileset = vec![0; 8];
+    tileset.extend_from_slice(&1u32.to_le_bytes()); // number of tiles
+    tileset.extend_from_slice(&16u16.to_le_bytes()); // tile width
+    tileset.extend_from_slice(&1u16.to_le_bytes()); // tile height
+    for bytes in &[chunk(0x2005, &cel), chunk(0x2023, &tileset), palette(0, 15)]
+    {
+        match Chunk::from_read_with_options(
+            &mut Cursor::new(bytes),
+            &header,
+            &limited,
+        ) {
+            Err(Error::InvalidData { message, .. }) => {
+                assert!(message.contains("over the limit"), "{}", message)
+            }
+            other => panic!("expected a limit to be hit, got {:?}", other),
+        }
+    }
+
+    // maps nested in maps, deeper than properties can go
+    let mut properties = vec![];
+    for _ in 0..100 {
+        properties.extend_from_slice(&1u32.to_le_bytes()); // properties
+        properties.extend_from_slice(&[0, 0]); // name
+        properties.extend_from_slice(&0x12u16.to_le_bytes()); // map
+    }
+    let mut user_data = 4u32.to_le_bytes().to_vec(); // HasProperties
+    user_data.extend_from_slice(&(properties.len() as u32 + 12).to_le_bytes());
+    user_data.extend_from_slice(&1u32.to_le_bytes()); // maps
+    user_data.extend_from_slice(&0u32.to_le_bytes()); // key
+    user_data.extend_from_slice(&properties);
+    match Chunk::from_read(&mut Cursor::new(chunk(0x2020, &user_data)), &header)
+    {
+        Err(Error::InvalidData { message, .. }) => {
+            assert!(message.contains("nested"), "{}", message)
+        }
+        other => panic!("expected nested properties error, got {:?}", other),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn lenient() -> std::io::Result<()> {
+    let mut frames = vec![];
+    for _ in 0..2 {
+        let mut frame = Frame::new();
+        frame
+            .add_chunk(Chunk::new(ChunkData::LayerChunk(LayerChunk::new(
+                "layer", true,
+            ))))
+            .add_chunk(Chunk::new(ChunkData::Unknown {
+                chunk_type: 0x2042,
+                bytes: vec![1, 2, 3],
+            }))
+            .add_chunk(Chunk::new(ChunkData::CelChunk(solid_cel(
+                0,
+                0,
+                0,
+                1,
+                1,
+                rgba(255, 0, 0, 255),
+            ))));
+        frames.push(frame);
+    }
+    let mut wtr = Cursor::new(vec![]);
+    Aseprite::new(Header::default(), frames).write(&mut wtr)?;
+    let bytes = wtr.into_inner();
+
+    let options = ReadOptions {
+        mode: ReadMode::Lenient,
+        ..ReadOptions::default()
+    };
+    let read = |bytes: &[u8]| {
+        Aseprite::from_read_with_options(&mut Cursor::new(bytes), &options)
+    };
+
+    let err = Aseprite::from_read(&mut Cursor::new(&bytes)).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::UnknownChunkType {
+                chunk_type: 0x2042,
+                ..
+            }
+        ),
+        "{:?}",
+        err
+    );
+
+    // unknown chunks are written back as they were read
+    let ase = read(&bytes)?;
+    assert!(ase.warnings.is_empty());
+    match &ase.frames[0].chunks[1].chunk_data {
+        ChunkData::Unknown { chunk_type, bytes } => {
+            assert_eq!((*chunk_type, &bytes[..]), (0x2042, &[1, 2, 3][..]))
+        }
+        other => panic!("expected unknown chunk, got {:?}", other),
+    }
+    let mut wtr = Cursor::new(vec![]);
+    ase.write(&mut wtr)?;
+    assert_eq!(wtr.into_inner(), bytes);
+
+    let frame1 = 128
+        + u32::from_le_bytes([bytes[128], bytes[129], bytes[130], bytes[131]])
+            as usize;
+    let layer1 = frame1 + 16;
+
+    // a layer that can't be read is skipped, the chunks after it are kept
+    let mut invalid_layer = bytes.clone();
+    invalid_layer[layer1 + 8] = 9;
+    let ase = read(&invalid_layer)?;
+    assert_eq!(ase.frames[0].chunks.len(), 3);
+    assert_eq!(ase.frames[1].chunks.len(), 2);
+    assert!(matches!(
+        ase.frames[1].chunks[1].chunk_data,
+        ChunkData::CelChunk(_)
+    ));
+    match &ase.warnings[..] {
+        [Error::InvalidEnumValue { location, .. }] => assert_eq!(
+            *location,
+            Location {
+                offset: Some(layer1 as u64),
+                frame: Some(1),
+                chunk: Some(0)
+            }
+        ),
+        other => panic!("expected invalid layer warning, got {:?}", other),
+    }
+    assert!(Aseprite::from_read(&mut Cursor::new(&invalid_layer)).is_err());
+
+    // a chunk size past the end of the frame skips the rest of the frame
+    let mut oversized = bytes.clone();
+    oversized[layer1..layer1 + 4].copy_from_slice(&0xFFFFu32.to_le_bytes());
+    let ase = read(&oversized)?;
+    assert_eq!(ase.frames.len(), 2);
+    assert_eq!(ase.frames[0].chunks.len(), 3);
+    assert!(ase.frames[1].chunks.is_empty());
+    match &ase.warnings[..] {
+        [Error::Truncated { location }] => {
+            assert_eq!((location.frame, location.chunk), (Some(1), Some(0)))
+        }
+        other => panic!("expected truncated warning, got {:?}", other),
+    }
+
+    Ok(())
+}
+
+/// RGB matrix/TRC ICC profile with the same curve for the three channels
+fn icc_profile(curve: &[u8], primaries: [[f64; 3]; 3]) -> Vec<u8> {
+    let fixed = |v: f64| ((v * 65536.0).round() as i32).to_be_bytes();
+    let mut xyz = vec![];
+    for primary in &primaries {
+        xyz.extend_from_slice(b"XYZ \0\0\0\0");
+        for v in primary {
+            xyz.extend_from_slice(&fixed(*v));
+        }
+    }
+
+    let mut icc = vec![0; 128];
+    icc[12..16].copy_from_slice(b"mntr");
+    icc[16..20].copy_from_slice(b"RGB ");
+    icc[20..24].copy_from_slice(b"XYZ ");
+    icc[36..40].copy_from_slice(b"acsp");
+    icc.extend_from_slice(&6u32.to_be_bytes());
+    let data_start = 132 + 6 * 12;
+    let tags: [(&[u8; 4], usize, usize); 6] = [
+        (b"rXYZ", data_start, 20),
+        (b"gXYZ", data_start + 20, 20),
+        (b"bXYZ", data_start + 40, 20),
+        (b"rTRC", data_start + 60, curve.len()),
+        (b"gTRC", data_start + 60, curve.len()),
+        (b"bTRC", data_start + 60, curve.len()),
+    ];
+    for (signature, offset, size) in &tags {
+        icc.extend_from_slice(*signature);
+        icc.extend_from_slice(&(*offset as u32).to_be_bytes());
+        icc.extend_from_slice(&(*size as u32).to_be_bytes());
+    }
+    icc.extend_from_slice(&xyz);
+    icc.extend_from_slice(curve);
+    let len = icc.len() as u32;
+    icc[0..4].copy_from_slice(&len.to_be_bytes());
+    icc
+}
+
+#[test]
+fn color_profile() -> std::io::Result<()> {
+    let srgb_primaries = [
+        [0.4361, 0.2225, 0.0139],
+        [0.3851, 0.7169, 0.0971],
+        [0.1431, 0.0606, 0.7141],
+    ];
+    let mut srgb_curve = b"para\0\0\0\0\0\x03\0\0".to_vec();
+    for v in &[2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045] {
+        srgb_curve.extend_from_slice(
+            &((v * 65536.0f64).round() as i32).to_be_bytes(),
+        );
+    }
+    let linear_curve = b"curv\0\0\0\0\0\0\0\0";
+
+    let chunk = Chunk::new(ChunkData::ColorProfileChunk(ColorProfileChunk {
+        profile_type: ProfileType::EmbeddedIccProfile,
+        flags: color_profile_chunk::Flags::empty(),
+        fixed_gamma: Fixed::from_f64(2.2),
+        icc_profile: icc_profile(&srgb_curve, srgb_primaries),
+    }));
+    let chunk = chunk_rw(&chunk, &Header::default())?;
+    let profile = match &chunk.chunk_data {
+        ChunkData::ColorProfileChunk(profile) => profile,
+        other => panic!("expected color profile, got {:?}", other),
+    };
+    assert_eq!(
+        profile.icc_profile,
+        icc_profile(&srgb_curve, srgb_primaries)
+    );
+    assert_eq!(profile.fixed_gamma, Fixed::from_f64(2.2));
+
+    let transform = profile.srgb_transform()?;
+    assert!(!transform.is_identity());
+    for v in 0..=255u8 {
+        let c = transform.convert(rgba(v, v / 2, 255 - v, 7));
+        for (converted, original) in
+            [(c.r, v), (c.g, v / 2), (c.b, 255 - v), (c.a, 7)]
+        {
+            assert!((converted as i32 - original as i32).abs() <= 1);
+        }
+    }
+
+    let linear =
+        SrgbTransform::from_icc(&icc_profile(linear_curve, srgb_primaries))?;
+    assert_eq!(
+        linear.convert(rgba(128, 0, 255, 255)),
+        rgba(188, 0, 255, 255)
+    );
+
+    let mut gray = icc_profile(linear_curve, srgb_primaries);
+    gray[16..20].copy_from_slice(b"GRAY");
+    assert!(matches!(
+        SrgbTransform::from_icc(&gray),
+        Err(Error::InvalidData { .. })
+    ));
+
+    // an sRGB profile with a gamma of 1 stores linear colors
+    let mut frame = Frame::new();
+    frame
+        .add_chunk(Chunk::new(ChunkData::ColorProfileChunk(
+            ColorProfileChunk {
+                profile_type: ProfileType::SRgb,
+                flags: color_profile_chunk::Flags::SpecialFixedGamma,
+                fixed_gamma: Fixed::ONE,
+                icc_profile: vec![],
+            },
+        )))
+        .add_chunk(Chunk::new(ChunkData::LayerChunk(LayerChunk::new(
+            "layer", true,
+        ))))
+        .add_chunk(Chunk::new(ChunkData::CelChunk(solid_cel(
+            0,
+            0,
+            0,
+            1,
+            1,
+            rgba(128, 0, 255, 255),
+        ))));
+    let sprite = Sprite::from_aseprite(ase_rw(&Aseprite::new(
+        Header::new(1, 1),
+        vec![frame],
+    ))?);
+    assert_eq!(
+        sprite.render_frame(0)?.get(0, 0),
+        Some(Pixel::RGBA(rgba(128, 0, 255, 255)))
+    );
+    assert_eq!(
+        sprite.render_frame_srgb(0)?.get(0, 0),
+        Some(Pixel::RGBA(rgba(188, 0, 255, 255)))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn cel_compression() -> std::io::Result<()> {
+    let cel = solid_cel(0, 0, 0, 16, 16, rgba(10, 20, 30, 255));
+    let expected = cel.image(&ColorDepth::RGBA)?;
+    let mut frame = Frame::new();
+    frame
+        .add_chunk(Chunk::new(ChunkData::LayerChunk(LayerChunk::new(
+            "layer", true,
+        ))))
+        .add_chunk(Chunk::new(ChunkData::CelChunk(cel)));
+    let ase = Aseprite::new(Header::new(16, 16), vec![frame]);
+
+    let write = |ase: &Aseprite, cel_compression| {
+        let mut wtr = Cursor::new(vec![]);
+        ase.write_with_options(&mut wtr, &WriteOptions { cel_compression })
+            .map(|_| wtr.into_inner())
+    };
+    fn first_cel(ase: &Aseprite) -> &Cel {
+        match &ase.frames[0].chunks[1].chunk_data {
+            ChunkData::CelChunk(cel) => &cel.cel,
+            other => panic!("expected cel, got {:?}", other),
+        }
+    }
+
+    let raw = write(&ase, CelCompression::Keep)?;
+    let compressed = write(&ase, CelCompression::Compress(9))?;
+    assert!(compressed.len() < raw.len() / 4);
+    let read = Aseprite::from_read(&mut Cursor::new(&compressed))?;
+    let cel = first_cel(&read);
+    assert!(matches!(cel, Cel::CompressedImage { .. }));
+    assert_eq!(cel.image(&ColorDepth::RGBA)?, expected);
+
+    let stored = write(&ase, CelCompression::Compress(0))?;
+    let read_stored = Aseprite::from_read(&mut Cursor::new(&stored))?;
+    assert_eq!(first_cel(&read_stored).image(&ColorDepth::RGBA)?, expected);
+
+    // decompressing gives back the file with raw cels
+    assert_eq!(write(&read, CelCompression::Decompress)?, raw);
+    assert_eq!(write(&read, CelCompression::Keep)?, compressed);
+    // without the color depth of the sprite, frames and chunks are written
+    // as they are
+    let mut frame_bytes = vec![];
+    read.frames[0].write(&mut frame_bytes)?;
+    assert_eq!(frame_bytes, &compressed[128..]);
+
+    let mut cel = solid_cel(0, 0, 0, 4, 4, rgba(1, 2, 3, 4));
+    cel.compress(6)?;
+    assert!(matches!(cel.cel, Cel::CompressedImage { .. }));
+    cel.decompress(&ColorDepth::RGBA)?;
+    assert!(matches!(cel.cel, Cel::RawCel { .. }));
+    assert_eq!(
+        cel.image(&ColorDepth::RGBA)?.unwrap().get(3, 3),
+        Some(Pixel::RGBA(rgba(1, 2, 3, 4)))
+    );
+    assert!(Cel::LinkedCel { frame_position: 0 }
+        .to_compressed(9)?
+        .is_none());
+
+    Ok(())
+}
+
+#[test]
+fn write_without_seek() -> std::io::Result<()> {
+    for fname in &[
+        "sample_aseprite_files/simple.aseprite",
+        "sample_aseprite_files/layered.aseprite",
+        "sample_aseprite_files/animated.aseprite",
+        "sample_aseprite_files/sliced.aseprite",
+    ] {
+        let bytes = std::fs::read(fname)?;
+        let ase = Aseprite::from_read(&mut Cursor::new(&bytes))?;
+
+        // a Vec can't seek, every byte has to be written
+        let mut wtr: Vec<u8> = vec![];
+        ase.write(&mut wtr)?;
+        assert_eq!(wtr, bytes);
+
+        // reserved fields are zeroed instead of keeping what was there
+        let mut wtr = Cursor::new(vec![0xFF; bytes.len()]);
+        ase.write(&mut wtr)?;
+        assert_eq!(wtr.into_inner(), bytes);
+    }
+    Ok(())
+}